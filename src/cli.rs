@@ -1,25 +1,124 @@
 use ipfs_api::{IpfsClient, TryFromUri};
-use itertools::Itertools;
 use log::debug;
+use multibase::Base;
+use serde_json::Value;
 use std::{
-	collections::HashMap,
 	convert::TryFrom,
 	env::{self, Args},
 	error::Error as StdError,
 	fmt,
-	fs::{File, OpenOptions},
-	io::{stderr, BufRead, BufReader, Write},
+	fs::{self, File, OpenOptions},
+	io::{self, stderr, BufRead, BufReader, Write},
+	mem,
+	path::PathBuf,
 	process,
-	process::{Child, Command as ProcCommand, Stdio},
+	process::{Child, Command as ProcCommand, ExitStatus, Stdio},
+	str::FromStr,
 	sync::mpsc,
 	thread,
+	time::Duration,
+};
+
+use url::Url;
+use web3::types::{Address, BlockNumber, U256};
+
+use super::{
+	config::{Config, Defaults},
+	wasm::ModuleOptions,
 };
 
 const CLI_NAME: &str = "./daowiz";
 const PRIVATE_KEY_ARG: &str = "DEPLOYMENT_PRIVATE_KEY";
 
+/// Environment variables used when their corresponding flags aren't specified.
+const ETH_RPC_URI_ARG: &str = "DAOWIZ_ETH_RPC_URI";
+const ETH_CHAIN_ID_ARG: &str = "DAOWIZ_ETH_CHAIN_ID";
+const CONTRACTS_DIR_ARG: &str = "DAOWIZ_CONTRACTS_DIR";
+
+/// A network that --chain can select, filling in a public RPC URL and chain
+/// id for it.
+struct Chain {
+	name: &'static str,
+	rpc_uri: &'static str,
+	id: u64,
+
+	// The value of --l2 that the chain needs, if any
+	l2: Option<&'static str>,
+}
+
+/// The networks that --chain knows about.
+const CHAINS: [Chain; 6] = [
+	Chain {
+		name: "mainnet",
+		rpc_uri: "https://ethereum-rpc.publicnode.com",
+		id: 1,
+		l2: None,
+	},
+	Chain {
+		name: "sepolia",
+		rpc_uri: "https://ethereum-sepolia-rpc.publicnode.com",
+		id: 11155111,
+		l2: None,
+	},
+	Chain {
+		name: "polygon",
+		rpc_uri: "https://polygon-rpc.com",
+		id: 137,
+		l2: None,
+	},
+	Chain {
+		name: "arbitrum",
+		rpc_uri: "https://arb1.arbitrum.io/rpc",
+		id: 42161,
+		l2: Some("arbitrum"),
+	},
+	Chain {
+		name: "optimism",
+		rpc_uri: "https://mainnet.optimism.io",
+		id: 10,
+		l2: Some("optimism"),
+	},
+	Chain {
+		name: "base",
+		rpc_uri: "https://mainnet.base.org",
+		id: 8453,
+		l2: Some("optimism"),
+	},
+];
+
+/// The IPFS gateway that links shown to people go through, unless
+/// overridden.
+const DEFAULT_GATEWAY: &str = "https://ipfs.io";
+
 /// The assumed IPFS URL, by default an in-process instance.
-const DEFAULT_IPFS_GATEWAY: &str = "http://127.0.0.1:5001/";
+pub(crate) const DEFAULT_IPFS_GATEWAY: &str = "http://127.0.0.1:5001/";
+
+/// The line an IPFS daemon logs once its API accepts requests, and how often
+/// a daemon logging to a file is checked for it.
+const IPFS_READY_LINE: &str = "API server listening";
+const IPFS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The name of the log file, in the temp directory, of an IPFS daemon left
+/// running by --keep-ipfs-alive.
+const IPFS_LOG_FILE: &str = "daowiz-ipfs.log";
+
+/// Details of the Beacon DAO, unless overridden by flags or the config
+const DEFAULT_NAME: &str = "Vision DAO";
+const DEFAULT_DESCRIPTION: &str =
+	"The Vision DAO is a DAO that governs the Beacon DAO layer of the Vision ecosystem.";
+const DEFAULT_SYMBOL: &str = "VIS";
+
+// 1_000_000 * 10^18
+const DEFAULT_SUPPLY: U256 = U256([2003764205206896640, 54210, 0, 0]);
+
+/// The number of decimals of the DAO's token assumed by --supply-tokens.
+const DEFAULT_DECIMALS: u32 = 18;
+
+/// The most blocks that list fetches at once, unless overridden.
+const DEFAULT_SCAN_CONCURRENCY: usize = 8;
+
+/// The most modules that may be installed at once, unless overridden.
+const DEFAULT_MAX_MODULES: usize = 64;
 
 /// Instructions for how to use the program.
 const USAGE: &str = " - creates a new Vision Beacon DAO with the specified \
@@ -27,38 +126,548 @@ default modules
 \tDEPLOYMENT_KEY (required) - an environment var specifying the ethereum \
 private key to use for deploying the DAO
 \t--eth-rpc-uri (required) - a flag specifying the http url of an EVM-\
-compatible node that daowiz will deploy the Beacon DAO to
+compatible node that daowiz will deploy the Beacon DAO to. Falls back to the \
+DAOWIZ_ETH_RPC_URI environment var
 \t--ipfs-rpc-uri (optional) - a flag specifying the http url of an IPFS node \
 that daowiz will deploy Beacon DAO metadata to. Uses an in-process IPFS node by \
 default
+\t--gateway (optional) - a flag specifying the http url of the IPFS gateway \
+that links to uploaded content are shown through. Doesn't affect where \
+content is uploaded. Defaults to https://ipfs.io
 \t--eth-chain-id (required) - a flag specifying the Ethereum blockchain to \
-interact with
+interact with. Falls back to the DAOWIZ_ETH_CHAIN_ID environment var
+\t--chain (optional) - a flag specifying a known network (mainnet, sepolia, \
+polygon, arbitrum, optimism, or base) whose public RPC URL and chain id are \
+used when --eth-rpc-uri and --eth-chain-id aren't specified by flag or by the \
+environment
 \t--contracts-dir (required) - a flag specifying the path to a directory \
 containing the built Beacon DAO contracts that will be used for deploying the \
-Beacon DAO";
+Beacon DAO. Falls back to the DAOWIZ_CONTRACTS_DIR environment var
+\t--artifact-path (optional) - a flag specifying the path of the Idea \
+contract's artifact, either absolute or relative to --contracts-dir. Defaults \
+to where --artifact-format puts it
+\t--artifact-format (optional) - a flag specifying the toolchain that built \
+the artifact: combined (contracts/Idea.sol/Idea.json), foundry \
+(out/Idea.sol/Idea.json, with the bytecode under bytecode.object), or \
+hardhat (artifacts/contracts/Idea.sol/Idea.json). Detected from the layout of \
+--contracts-dir by default
+\t--bytecode-file (optional) - a flag specifying the path to a .bin file \
+containing the Idea contract's bytecode as hex, which is used instead of the \
+artifact's bytecode. The ABI is still read from the artifact
+\t--upload-cache (optional) - a flag specifying the path to a file recording \
+the CID's of previously uploaded modules, so unchanged modules aren't \
+re-uploaded
+\t--keep-ipfs-alive (optional) - a flag indicating that the IPFS node spawned \
+when no --ipfs-rpc-uri is specified should be left running once daowiz exits, \
+so that the uploaded content stays available. Its PID and log file are \
+printed so that it can be checked on and stopped later
+\t--replication-check (optional) - a flag specifying the API URL of a second \
+IPFS node that is asked to fetch each uploaded module, warning about any it \
+can't fetch within 30 seconds
+\t--require-replication (optional) - a flag indicating that modules the \
+--replication-check node can't fetch should fail the deployment
+\t--check-ipfs-storage (optional) - a flag indicating that the IPFS node's repo \
+should be checked for room before the modules are uploaded, warning if it \
+would be nearly full or can't be checked
+\t--print-metadata (optional) - a flag indicating that the DAO's metadata, \
+with the CID's of its modules, should be printed to stderr as JSON before it \
+is uploaded
+\t--validate-modules (optional) - a flag indicating that each module should be \
+run through a WASM validator before it is uploaded
+\t--validate-loaders (optional) - a flag indicating that each module's JS \
+loader should be parsed before it is uploaded, rejecting loaders with syntax \
+errors or without any exports
+\t--strict-modules (optional) - a flag indicating that a module whose loader \
+and WASM are the same file should be rejected, rather than warned about
+\t--require-export (optional) - a flag specifying the name of a function that \
+every module must export. Can be specified multiple times
+\t--strip-wasm (optional) - a flag indicating that custom sections should be \
+removed from each module before it is uploaded
+\t--keep-wasm-names (optional) - a flag indicating that the \"name\" custom \
+section should be kept when stripping modules
+\t--metadata-only (optional) - a flag indicating that only the DAO's metadata \
+should be uploaded, printing its CID instead of deploying the DAO. No chain \
+configuration or private key is required
+\t--metadata-cid (optional) - a flag specifying the CID of metadata that \
+already exists on IPFS to deploy the DAO with, instead of uploading the \
+specified modules
+\t--simulate-only (optional) - a flag indicating that the deployment should \
+only be simulated: the modules are checked, the metadata CID is computed \
+without uploading anything, and the gas is estimated without sending anything
+\t--at-block (optional) - a flag specifying the block number, latest, or \
+pending, that the deployer's balance is checked against, and that the gas is \
+estimated against with --simulate-only. The deployment is still sent against \
+the latest state, with a fixed gas limit
+\t--name (optional) - a flag specifying the name of the DAO
+\t--symbol (optional) - a flag specifying the symbol of the DAO's token
+\t--description (optional) - a flag specifying a markdown description of the \
+DAO
+\t--description-file (optional) - a flag specifying the path to a UTF-8 \
+markdown file containing the description of the DAO. Takes precedence over \
+--description
+\t--compress-description (optional) - a flag indicating that the description \
+should be uploaded as a gzipped file that the metadata links to, rather than \
+stored in the metadata itself
+\t--metadata-schema-version (optional) - a flag specifying the version of the \
+metadata format recorded in the metadata's schema_version. Defaults to 1
+\t--icon (optional) - a flag specifying the path to a PNG, JPEG, GIF, WebP, or \
+SVG image of at most 1 MiB that is uploaded as the DAO's icon
+\t--supply (optional) - a flag specifying the initial supply of the DAO's \
+token, in its smallest unit
+\t--supply-tokens (optional) - a flag specifying the initial supply of the \
+DAO's token in whole tokens, which may have a fractional part. Takes \
+precedence over --supply
+\t--decimals (optional) - a flag specifying the number of decimals of the \
+DAO's token, used to convert --supply-tokens to its smallest unit. Defaults to \
+18
+\t--config (optional) - a flag specifying the path to a TOML file whose \
+[defaults] section provides the name, symbol, description, and supply of the \
+DAO when their flags aren't specified
+\t--state-file (optional) - a flag specifying the path to a file that the \
+metadata CID and pending transaction hash are written to if the deployment is \
+interrupted with Ctrl-C
+\t--deploy-timeout (optional) - a flag specifying the maximum number of \
+seconds the whole deployment may take, including uploading metadata and \
+waiting for confirmations, but not waiting for --plan to be approved
+\t--confirmations (optional) - a flag specifying the number of blocks that must \
+be built on the deployment before it succeeds. Defaults to 2
+\t--confirm-seconds (optional) - a flag specifying the number of seconds the \
+deployment must stay in the same block before it succeeds, instead of \
+--confirmations
+\t--max-modules (optional) - a flag specifying the most modules that may be \
+installed at once. Defaults to 64
+\t--max-total-module-bytes (optional) - a flag specifying the most bytes that \
+the loaders and modules may total
+\t--max-module-bytes (optional) - a flag specifying the most bytes that any \
+one loader or module may be
+\t--plan (optional) - a flag indicating that a summary of the deployment should \
+be printed after the preflight checks, as JSON with --output json, and \
+approved before anything is uploaded or sent
+\t--l2 (optional) - a flag specifying the L2 being deployed to, either \
+arbitrum or optimism, so that its fees are accounted for. Arbitrum deployments \
+get a higher gas limit covering L1 calldata, and OP-stack deployments budget \
+for the L1 data fee when checking the deployer's balance
+\t--export-csv (optional) - a flag specifying the path to a CSV file that the \
+chain id, address, transaction hash, metadata CID, and timestamp of the \
+deployment are appended to
+\t--output (optional) - a flag specifying whether to print the address of the \
+DAO as text, along with a summary of the gas used to stderr, or the details of \
+the deployment as json
+\t--deployer (optional) - a flag specifying the address, or ENS name, that the \
+private key is expected to belong to. The deployment is aborted if it doesn't
+\t--recipient (optional) - a flag specifying the address, or ENS name, that \
+the DAO's initial supply should go to. Defaults to the deployer
+\t--constructor-args (optional) - a flag specifying a JSON array of the \
+arguments to deploy the contract with, instead of the DAO's name, symbol, \
+supply, and metadata CID. Each is converted to the type the ABI declares for \
+its parameter: address, uint, string, or bool. Requires a --metadata-cid, \
+since no metadata is uploaded for them
+\t--strict-cid (optional) - a flag indicating that the deployment should be \
+aborted, rather than warned about, if the contract can't store the whole \
+metadata CID
+\t--access-list (optional) - a flag specifying the path to a JSON file \
+containing an EIP-2930 access list of {address, storageKeys} entries, which is \
+attached to the deployment transaction
+\t--modules-dir (optional) - a flag specifying a directory whose loaders and \
+modules should be installed, alongside any specified by path. Can be \
+specified multiple times, but a module may only be in one of the directories";
 
 /// Required args to the command-line application.
 pub(crate) struct Context {
 	pub(crate) cmd: Command,
 }
 
+/// The JS loader and WASM module found for a module, in that order.
+type Slot = (Option<File>, Option<File>);
+
 #[derive(Default)]
 struct ContextBuilder {
 	cmd: Option<CommandBuilder>,
 
 	eth_uri: Option<String>,
 	eth_chain_id: Option<String>,
+	chain: Option<String>,
 	ipfs_uri: Option<String>,
+	gateway: Option<String>,
 	contracts_dir: Option<String>,
+	artifact_path: Option<String>,
+	bytecode_file: Option<String>,
+	artifact_format: Option<String>,
 	private_key: Option<String>,
+	upload_cache: Option<String>,
+	keep_ipfs_alive: bool,
+	replication_check: Option<String>,
+	require_replication: bool,
+	check_ipfs_storage: bool,
+	print_metadata: bool,
+	validate_modules: bool,
+	validate_loaders: bool,
+	strict_modules: bool,
+	required_exports: Vec<String>,
+	strip_wasm: bool,
+	keep_wasm_names: bool,
+	metadata_only: bool,
+	metadata_cid: Option<String>,
+	simulate_only: bool,
+	at_block: Option<String>,
+
+	name: Option<String>,
+	symbol: Option<String>,
+	description: Option<String>,
+	description_file: Option<String>,
+	compress_description: bool,
+	metadata_schema_version: Option<String>,
+	icon: Option<String>,
+	supply: Option<String>,
+	supply_tokens: Option<String>,
+	decimals: Option<String>,
+	config: Option<String>,
+	state_file: Option<String>,
+	deploy_timeout: Option<String>,
+	confirmations: Option<String>,
+	confirm_seconds: Option<String>,
+	plan: bool,
+	l2: Option<String>,
+	quiet: bool,
+	checkpoint: Option<String>,
+	scan_concurrency: Option<String>,
+	limit: Option<String>,
+	offset: Option<String>,
+	list_timeout: Option<String>,
+	strict: bool,
+	max_modules: Option<String>,
+	max_total_module_bytes: Option<String>,
+	max_module_bytes: Option<String>,
+	output: Option<String>,
+	resolve_metadata: bool,
+	stream: bool,
+	export_csv: Option<String>,
+	deployer: Option<String>,
+	recipient: Option<String>,
+	constructor_args: Option<String>,
+	strict_cid: bool,
+	access_list: Option<String>,
+
+	// Paths of the modules' files, as specified
+	module_paths: Vec<String>,
+
+	// Directories whose modules are installed before those specified by path
+	modules_dirs: Vec<String>,
+
+	// Modules are kept in the order they were specified, so that the
+	// uploaded metadata is identical across runs
+	files: Vec<(String, Slot)>,
+}
+
+impl ContextBuilder {
+	/// Gets the slot storing the js loader and wasm module of the named
+	/// module, creating an empty slot if the module hasn't been seen yet.
+	fn slot(&mut self, name: &str) -> &mut Slot {
+		let i = match self.files.iter().position(|(n, _)| n == name) {
+			Some(i) => i,
+			None => {
+				self.files.push((name.to_owned(), (None, None)));
+
+				self.files.len() - 1
+			}
+		};
+
+		&mut self.files[i].1
+	}
+
+	/// Adds the loaders and modules in each of the specified module
+	/// directories to the module paths, in the order the directories were
+	/// specified. A module found in two directories is rejected, since only
+	/// one of them could be installed.
+	fn discover_modules(&mut self) -> Result<(), ParseError> {
+		// The directory each module was first found in
+		let mut seen: Vec<(String, String)> = Vec::new();
+		let mut paths = Vec::new();
+
+		for dir in mem::take(&mut self.modules_dirs) {
+			let mut entries: Vec<_> = fs::read_dir(&dir)
+				.and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).try_collect())
+				.map_err(|e| ParseError::MiscError(Box::new(e)))?;
+
+			// Directories aren't listed in any particular order, but the
+			// metadata should be identical across runs
+			entries.sort();
+
+			for path in entries {
+				let fname = path.to_string_lossy().into_owned();
+				if !fname.ends_with(".wasm") && !fname.ends_with(".js") {
+					continue;
+				}
+
+				let name = path
+					.file_name()
+					.unwrap_or_default()
+					.to_string_lossy()
+					.trim_end_matches(".wasm")
+					.trim_end_matches(".js")
+					.trim_end_matches("_bg")
+					.to_owned();
+
+				match seen.iter().find(|(seen_name, _)| *seen_name == name) {
+					Some((_, first)) if *first != dir => {
+						return Err(ParseError::ModuleCollision(name, first.clone(), dir))
+					}
+					Some(_) => {}
+					None => seen.push((name, dir.clone())),
+				}
+
+				paths.push(fname);
+			}
+		}
+
+		paths.append(&mut self.module_paths);
+		self.module_paths = paths;
+
+		Ok(())
+	}
+
+	/// Opens the specified module files, sorting them into the slots of their
+	/// modules. Files that don't end with .wasm or .js, or that can't be
+	/// opened, are skipped.
+	fn open_modules(&mut self) -> Result<(), ParseError> {
+		let max_bytes = parse_limit::<u64>("--max-module-bytes", self.max_module_bytes.take())?;
+
+		for fname in mem::take(&mut self.module_paths) {
+			// Get slot storing js loader and wasm module
+			let stripped = fname
+				.trim_end_matches(".wasm")
+				.trim_end_matches(".js")
+				.trim_end_matches("_bg");
+
+			if let Ok(f) = OpenOptions::new().read(true).open(&fname) {
+				// A single huge file is usually a misbuild
+				if let Some(max_bytes) = max_bytes {
+					let size = f
+						.metadata()
+						.map_err(|e| ParseError::MiscError(Box::new(e)))?
+						.len();
+
+					if size > max_bytes {
+						return Err(ParseError::ModuleTooLarge(fname, size, max_bytes));
+					}
+				}
+
+				let slot = self.slot(stripped);
+
+				// Sort encountered files by loader, or module type
+				if fname.ends_with(".wasm") {
+					slot.1 = Some(f);
+				} else if fname.ends_with(".js") {
+					slot.0 = Some(f);
+				}
+			}
+		}
+
+		Ok(())
+	}
 
-	files: HashMap<String, (Option<File>, Option<File>)>,
+	/// Takes the configuration necessary for uploading the DAO's metadata,
+	/// spawning an IPFS node if no IPFS host was specified.
+	fn take_metadata(&mut self) -> Result<MetadataContext, ParseError> {
+		// Transform paths into file contents, bubbling IO errors
+		let modules: Vec<Module> = self
+			.files
+			.drain(..)
+			.filter_map(|(name, tup): (String, Slot)| match tup {
+				(Some(loader), Some(module)) => Some(Module {
+					name,
+					loader,
+					module,
+				}),
+				_ => None,
+			})
+			.collect();
+
+		// Catch typos in globs that would deploy far more modules than
+		// intended
+		let max_modules =
+			parse_limit("--max-modules", self.max_modules.take())?.unwrap_or(DEFAULT_MAX_MODULES);
+		if modules.len() > max_modules {
+			return Err(ParseError::TooManyModules(modules.len(), max_modules));
+		}
+
+		// Likewise for globs that pick up huge artifacts
+		if let Some(max_bytes) = parse_limit::<u64>(
+			"--max-total-module-bytes",
+			self.max_total_module_bytes.take(),
+		)? {
+			let mut total = 0;
+			for Module { loader, module, .. } in &modules {
+				for f in [loader, module] {
+					total += f
+						.metadata()
+						.map_err(|e| ParseError::MiscError(Box::new(e)))?
+						.len();
+				}
+			}
+
+			if total > max_bytes {
+				return Err(ParseError::ModulesTooLarge(total, max_bytes));
+			}
+		}
+
+		Ok(MetadataContext {
+			title: self.name.take().unwrap_or_else(|| DEFAULT_NAME.to_owned()),
+			description: match self.description_file.take() {
+				Some(path) => fs::read(&path)
+					.map_err(|e| ParseError::InvalidDescriptionFile(path.clone(), Box::new(e)))
+					.and_then(|src| {
+						String::from_utf8(src)
+							.map_err(|e| ParseError::InvalidDescriptionFile(path, Box::new(e)))
+					})?,
+				None => self
+					.description
+					.take()
+					.unwrap_or_else(|| DEFAULT_DESCRIPTION.to_owned()),
+			},
+			compress_description: self.compress_description,
+			icon: self.icon.take(),
+			schema_version: self
+				.metadata_schema_version
+				.take()
+				.map(|version| {
+					version
+						.parse()
+						.map_err(|_| ParseError::InvalidSchemaVersion(version))
+				})
+				.transpose()?,
+
+			modules,
+			module_options: ModuleOptions {
+				validate: self.validate_modules,
+				required_exports: mem::take(&mut self.required_exports),
+				strip: self.strip_wasm,
+				keep_names: self.keep_wasm_names,
+				validate_loaders: self.validate_loaders,
+				strict: self.strict_modules,
+			},
+
+			// Spawned once every other flag has been validated
+			ipfs_handle: None,
+			ipfs: self.ipfs_client()?,
+			ipfs_uri: self
+				.ipfs_uri
+				.clone()
+				.unwrap_or_else(|| DEFAULT_IPFS_GATEWAY.to_owned()),
+			upload_cache: self.upload_cache.take(),
+			keep_ipfs_alive: self.keep_ipfs_alive,
+			hash_only: self.simulate_only,
+			print_metadata: self.print_metadata,
+			replication_check: self
+				.replication_check
+				.take()
+				.map(|uri| IpfsClient::from_str(&uri))
+				.transpose()
+				.map_err(|e| ParseError::MiscError(Box::new(e)))?,
+			require_replication: self.require_replication,
+			check_ipfs_storage: self.check_ipfs_storage,
+		})
+	}
+
+	/// Takes the location of the Idea contract's artifact.
+	fn take_artifact(&mut self) -> Result<Artifact, ParseError> {
+		Ok(Artifact {
+			contracts_dir: self
+				.contracts_dir
+				.take()
+				.ok_or(ParseError::MissingContractsSrc)?,
+			path: self.artifact_path.take(),
+			bytecode_file: self.bytecode_file.take(),
+			format: match self.artifact_format.take().as_deref() {
+				Some("combined") => Some(ArtifactFormat::Combined),
+				Some("foundry") => Some(ArtifactFormat::Foundry),
+				Some("hardhat") => Some(ArtifactFormat::Hardhat),
+				None => None,
+				Some(format) => return Err(ParseError::InvalidArtifactFormat(format.to_owned())),
+			},
+		})
+	}
+
+	/// Gets a client for the specified IPFS node, or the in-process node if
+	/// no host was specified.
+	fn ipfs_client(&self) -> Result<IpfsClient, ParseError> {
+		IpfsClient::from_str(self.ipfs_uri.as_deref().unwrap_or(DEFAULT_IPFS_GATEWAY))
+			.map_err(|e| ParseError::MiscError(Box::new(e)))
+	}
 }
 
 /// Command-specific configuration options.
 pub enum Command {
 	New(Box<NewContext>),
+	Metadata(Box<MetadataContext>),
 	List(ListContext),
+	Doctor(DoctorContext),
+	Modules(ModulesContext),
+	Abi(AbiContext),
+	Version(VersionContext),
+}
+
+impl Command {
+	/// Gets the configuration for uploading the DAO's metadata, if the command
+	/// uploads any.
+	fn upload_context_mut(&mut self) -> Option<&mut MetadataContext> {
+		match self {
+			Self::New(ctx) => match &mut ctx.metadata {
+				Metadata::Upload(ctx) => Some(ctx),
+				Metadata::Existing(_) => None,
+			},
+			Self::Metadata(ctx) => Some(ctx),
+			Self::List(_)
+			| Self::Doctor(_)
+			| Self::Modules(_)
+			| Self::Abi(_)
+			| Self::Version(_) => None,
+		}
+	}
+
+	/// Gets the handle to the IPFS node running in the background, if one was
+	/// spawned for the command.
+	pub(crate) fn take_ipfs_handle(&mut self) -> Option<Child> {
+		match self {
+			Self::New(ctx) => ctx.metadata.take_ipfs_handle(),
+			Self::Metadata(ctx) => ctx.ipfs_handle.take(),
+			Self::List(_)
+			| Self::Doctor(_)
+			| Self::Modules(_)
+			| Self::Abi(_)
+			| Self::Version(_) => None,
+		}
+	}
+
+	/// Whether the IPFS node running in the background should be left
+	/// running once the command exits.
+	pub(crate) fn keep_ipfs_alive(&self) -> bool {
+		match self {
+			Self::New(ctx) => {
+				matches!(&ctx.metadata, Metadata::Upload(ctx) if ctx.keep_ipfs_alive)
+			}
+			Self::Metadata(ctx) => ctx.keep_ipfs_alive,
+			Self::List(_)
+			| Self::Doctor(_)
+			| Self::Modules(_)
+			| Self::Abi(_)
+			| Self::Version(_) => false,
+		}
+	}
+
+	/// Gets the file that the progress of the command should be written to if
+	/// it is interrupted.
+	pub(crate) fn take_state_file(&mut self) -> Option<String> {
+		match self {
+			Self::New(ctx) => ctx.state_file.take(),
+			Self::Metadata(_)
+			| Self::List(_)
+			| Self::Doctor(_)
+			| Self::Modules(_)
+			| Self::Abi(_)
+			| Self::Version(_) => None,
+		}
+	}
 }
 
 /// Configuration variables necessary for executing the `new` command.
@@ -66,15 +675,203 @@ pub struct NewContext {
 	pub(crate) private_key: String,
 	pub(crate) eth_uri: String,
 	pub(crate) eth_chain_id: u64,
-	pub(crate) contracts_dir: String,
+	pub(crate) artifact: Artifact,
+
+	// Details of the DAO's token
+	pub(crate) name: String,
+	pub(crate) symbol: String,
+	pub(crate) supply: U256,
+
+	pub(crate) metadata: Metadata,
+
+	// File the progress of the deployment is written to if interrupted
+	pub(crate) state_file: Option<String>,
+
+	// The longest the whole deployment may take
+	pub(crate) deploy_timeout: Option<Duration>,
+
+	// Whether the deployment should be described and approved before
+	// anything is uploaded or sent
+	pub(crate) plan: bool,
+
+	// The L2 being deployed to, if its fees work differently than Ethereum's
+	pub(crate) l2: Option<L2>,
+
+	// CSV file the deployment is appended to
+	pub(crate) export_csv: Option<String>,
+
+	// The hex address or ENS name the private key is expected to belong to
+	pub(crate) deployer: Option<String>,
+
+	// The hex address or ENS name the supply is minted to, instead of the
+	// deployer
+	pub(crate) recipient: Option<String>,
+
+	// Whether a metadata CID that the contract would cut off is an error
+	pub(crate) strict_cid: bool,
+
+	// Arguments passed to the constructor in place of the DAO's details, for
+	// contracts whose constructors take something else
+	pub(crate) constructor_args: Option<Vec<Value>>,
+
+	// Whether the deployment is only simulated, without storing or sending
+	// anything
+	pub(crate) simulate_only: bool,
+
+	// The block that the deployer's balance is checked and the gas is
+	// estimated against, rather than the latest
+	pub(crate) at_block: Option<BlockNumber>,
+
+	// The HTTP gateway that links to content on IPFS are shown through
+	pub(crate) gateway: String,
+
+	// JSON file containing an access list for the deployment transaction
+	pub(crate) access_list: Option<String>,
+
+	// When the deployment is considered final, if not after the default
+	// number of confirmations
+	pub(crate) confirmation: Option<Confirmation>,
+
+	pub(crate) output: OutputFormat,
+}
+
+/// When a deployment is considered final.
+#[derive(Clone, Copy)]
+pub enum Confirmation {
+	// Once the number of blocks have been built on it
+	Blocks(usize),
+
+	// Once it has stayed in the same block for the duration
+	Elapsed(Duration),
+}
+
+/// L2 networks whose fees need special handling.
+#[derive(Clone, Copy)]
+pub enum L2 {
+	Arbitrum,
+
+	// Any OP-stack chain
+	Optimism,
+}
+
+/// Where the metadata that the DAO is deployed with comes from.
+pub enum Metadata {
+	// Metadata assembled from the specified modules, and uploaded to IPFS
+	Upload(Box<MetadataContext>),
+
+	// The CID of metadata that already exists on IPFS
+	Existing(String),
+}
+
+impl Metadata {
+	/// Gets the handle to the IPFS node running in the background, if one was
+	/// spawned to upload the metadata.
+	pub(crate) fn take_ipfs_handle(&mut self) -> Option<Child> {
+		match self {
+			Self::Upload(ctx) => ctx.ipfs_handle.take(),
+			Self::Existing(_) => None,
+		}
+	}
+}
+
+/// Configuration variables necessary for uploading the DAO's metadata. Used
+/// alone by `new --metadata-only`.
+pub struct MetadataContext {
+	pub(crate) title: String,
+	pub(crate) description: String,
+
+	// Whether the description is uploaded separately, gzipped
+	pub(crate) compress_description: bool,
+
+	// Path of the image uploaded as the DAO's icon
+	pub(crate) icon: Option<String>,
+
+	// The version of the metadata format recorded, if not the current one
+	pub(crate) schema_version: Option<u32>,
 
 	// Handles to all of the specified modules
-	pub(crate) modules: Vec<(File, File)>,
+	pub(crate) modules: Vec<Module>,
+	pub(crate) module_options: ModuleOptions,
 
 	// IPFS Node that might be running in the background if no proxy URL was
 	// provided
 	pub(crate) ipfs: IpfsClient,
+	pub(crate) ipfs_uri: String,
 	pub(crate) ipfs_handle: Option<Child>,
+
+	// File recording the CID's of previously uploaded content
+	pub(crate) upload_cache: Option<String>,
+
+	// Whether the IPFS node in the background outlives the program
+	pub(crate) keep_ipfs_alive: bool,
+
+	// Whether the CID's of the metadata are computed without storing it
+	pub(crate) hash_only: bool,
+
+	// Whether the metadata is printed for review before it's stored
+	pub(crate) print_metadata: bool,
+
+	// A second IPFS node that should be able to fetch the uploaded modules,
+	// and whether failing to is an error rather than a warning
+	pub(crate) replication_check: Option<IpfsClient>,
+	pub(crate) require_replication: bool,
+
+	// Whether the IPFS node is checked for room before anything is uploaded
+	pub(crate) check_ipfs_storage: bool,
+}
+
+/// A module to install in the Beacon DAO, made up of a JS loader and the WASM
+/// it loads.
+pub struct Module {
+	// The path of the module, without its extension
+	pub(crate) name: String,
+
+	pub(crate) loader: File,
+	pub(crate) module: File,
+}
+
+/// Where the compiled Idea contract is loaded from.
+pub struct Artifact {
+	pub(crate) contracts_dir: String,
+
+	// Overrides the location of the artifact in the contracts dir. Absolute
+	// paths are used as-is
+	pub(crate) path: Option<String>,
+
+	// A .bin file containing the contract's bytecode, which is otherwise
+	// read from the artifact
+	pub(crate) bytecode_file: Option<String>,
+
+	// Detected from the contracts dir's layout if not specified
+	pub(crate) format: Option<ArtifactFormat>,
+}
+
+/// The toolchain that built the artifact, which determines where it is and
+/// how its fields are laid out.
+#[derive(Clone, Copy, Debug)]
+pub enum ArtifactFormat {
+	// The bytecode is a hex string
+	Combined,
+
+	// The bytecode is a hex string under bytecode.object
+	Foundry,
+
+	// The bytecode is a hex string, alongside the deployed bytecode
+	Hardhat,
+}
+
+impl ArtifactFormat {
+	pub(crate) const ALL: [Self; 3] = [Self::Combined, Self::Foundry, Self::Hardhat];
+
+	/// Gets where the toolchain puts the Idea contract's artifact in the
+	/// contracts dir.
+	pub(crate) fn default_path(&self) -> &'static str {
+		match self {
+			Self::Combined => "contracts/Idea.sol/Idea.json",
+			Self::Foundry => "out/Idea.sol/Idea.json",
+			Self::Hardhat => "artifacts/contracts/Idea.sol/Idea.json",
+		}
+	}
 }
 
 /// Configuration variables necessary for executing the `list` command.
@@ -82,7 +879,89 @@ pub struct ListContext {
 	pub(crate) private_key: String,
 	pub(crate) eth_uri: String,
 	pub(crate) eth_chain_id: u64,
-	pub(crate) contracts_dir: String,
+	pub(crate) artifact: Artifact,
+
+	// Whether the progress of the scan should be hidden
+	pub(crate) quiet: bool,
+
+	// File the position of the scan is recorded in, so that it can be
+	// resumed if interrupted
+	pub(crate) checkpoint: Option<String>,
+
+	// The most blocks fetched at once
+	pub(crate) scan_concurrency: usize,
+
+	// The page of deployments listed, counting from the newest
+	pub(crate) limit: Option<usize>,
+	pub(crate) offset: usize,
+
+	// The longest the scan may take, and whether running out of time is an
+	// error rather than listing what was found
+	pub(crate) list_timeout: Option<Duration>,
+	pub(crate) strict: bool,
+
+	pub(crate) output: OutputFormat,
+
+	// Whether each contract is printed as soon as it's found
+	pub(crate) stream: bool,
+
+	// Whether the metadata CID of each contract should be read
+	pub(crate) resolve_metadata: bool,
+
+	// CSV file the deployments are written to
+	pub(crate) export_csv: Option<String>,
+}
+
+/// Configuration variables necessary for executing the `doctor` command.
+pub struct DoctorContext {
+	pub(crate) eth_uri: String,
+	pub(crate) eth_chain_id: u64,
+	pub(crate) artifact: Artifact,
+
+	// The IPFS node is checked as-is, without spawning one in the background
+	pub(crate) ipfs: IpfsClient,
+}
+
+/// Configuration variables necessary for executing the `modules` command.
+pub struct ModulesContext {
+	pub(crate) target: ModulesTarget,
+	pub(crate) output: OutputFormat,
+	pub(crate) gateway: String,
+
+	// Like doctor, no IPFS node is spawned
+	pub(crate) ipfs: IpfsClient,
+}
+
+/// Configuration variables necessary for executing the `abi` command.
+pub struct AbiContext {
+	pub(crate) artifact: Artifact,
+}
+
+/// Configuration variables necessary for executing the `version` command.
+pub struct VersionContext {
+	// Only read if a contracts dir was specified
+	pub(crate) artifact: Option<Artifact>,
+	pub(crate) output: OutputFormat,
+}
+
+/// The DAO whose modules are listed.
+pub enum ModulesTarget {
+	// A deployed DAO, whose metadata CID is read from the contract
+	Contract {
+		address: Address,
+		eth_uri: String,
+		artifact: Artifact,
+	},
+
+	// The CID of a DAO's metadata
+	Metadata(String),
+}
+
+/// How results are printed.
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+	Text,
+	Json,
 }
 
 impl TryFrom<ContextBuilder> for Command {
@@ -91,92 +970,214 @@ impl TryFrom<ContextBuilder> for Command {
 	/// Unwraps fields from a configuration, returning an error if a required
 	/// field was not specified. Uses defaults for relevant fields.
 	fn try_from(mut v: ContextBuilder) -> Result<Self, Self::Error> {
-		match v.cmd {
+		// Simulations compute their CID's without a node
+		let needs_ipfs_node = v.ipfs_uri.is_none() && !v.simulate_only;
+
+		let mut cmd = match v.cmd {
+			Some(CommandBuilder::New) if v.metadata_only => {
+				Ok(Self::Metadata(Box::new(v.take_metadata()?)))
+			}
 			Some(CommandBuilder::New) => Ok(Self::New(Box::new(NewContext {
-				private_key: v.private_key.ok_or(ParseError::MissingPrivateKey)?,
-				eth_uri: v.eth_uri.ok_or(ParseError::MissingRpcUrlETH)?,
+				private_key: v.private_key.take().ok_or(ParseError::MissingPrivateKey)?,
+				eth_uri: rpc_url(v.eth_uri.take())?,
 				eth_chain_id: v
 					.eth_chain_id
+					.take()
 					.ok_or(ParseError::MissingChainId)?
 					.parse()
 					.map_err(|_| ParseError::MissingChainId)?,
-				contracts_dir: v.contracts_dir.ok_or(ParseError::MissingContractsSrc)?,
-				// Transform paths into file contents, bubbling IO errors
-				modules: v
-					.files
-					.drain()
-					.filter_map(
-						|(_, tup): (String, (Option<File>, Option<File>))| match tup {
-							(Some(a), Some(b)) => Some((a, b)),
-							_ => None,
-						},
-					)
-					.collect(),
-
-				// Spawn an IPFS node if the user didn't specify a host
-				ipfs_handle: if v.ipfs_uri.is_none() {
-					let (tx, rx) = mpsc::channel();
-
-					log::debug!("starting IPFS daemon");
-
-					thread::spawn(move || {
-						let mut cmd = ProcCommand::new("ipfs")
-							.arg("daemon")
-							.stdout(Stdio::piped())
-							.stderr(Stdio::piped())
-							.spawn()
-							.map_err(|e| ParseError::MiscError(Box::new(e)))
-							.unwrap();
-
-						let out = cmd.stdout.take().unwrap();
-						let reader = BufReader::new(out);
-						let mut lines = reader.lines().map(Result::unwrap);
-
-						for l in lines.by_ref() {
-							debug!("{l}");
-
-							if l.contains("API server listening") {
-								tx.send(cmd).unwrap();
-								break;
-							}
-						}
-
-						loop {
-							lines.next();
-						}
-					});
-
-					Some(rx.recv().map_err(|e| ParseError::MiscError(Box::new(e)))?)
-				} else {
-					None
+				artifact: v.take_artifact()?,
+				name: v.name.clone().unwrap_or_else(|| DEFAULT_NAME.to_owned()),
+				symbol: v.symbol.take().unwrap_or_else(|| DEFAULT_SYMBOL.to_owned()),
+				supply: match (v.supply_tokens.take(), v.supply.take()) {
+					(Some(amount), _) => {
+						let decimals = match v.decimals.take() {
+							Some(decimals) => decimals
+								.parse()
+								.map_err(|_| ParseError::InvalidDecimals(decimals))?,
+							None => DEFAULT_DECIMALS,
+						};
+
+						token_supply(&amount, decimals)?
+					}
+					(None, Some(supply)) => U256::from_dec_str(&supply)
+						.map_err(|_| ParseError::InvalidSupply(supply))?,
+					(None, None) => DEFAULT_SUPPLY,
+				},
+
+				// IPFS is never needed if the metadata already exists. Specified
+				// arguments needn't include the CID, so nothing is uploaded for
+				// them
+				metadata: match v.metadata_cid.take() {
+					Some(cid) if is_cid(&cid) => Metadata::Existing(cid),
+					Some(cid) => return Err(ParseError::InvalidMetadataCid(cid)),
+					None if v.constructor_args.is_some() => {
+						return Err(ParseError::RequiredFlag(
+							"--constructor-args",
+							"--metadata-cid",
+						))
+					}
+					None => Metadata::Upload(Box::new(v.take_metadata()?)),
+				},
+				state_file: v.state_file.take(),
+				deploy_timeout: v
+					.deploy_timeout
+					.take()
+					.map(|secs| {
+						secs.parse()
+							.map(Duration::from_secs)
+							.map_err(|_| ParseError::InvalidTimeout(secs))
+					})
+					.transpose()?,
+				plan: v.plan,
+				l2: match v.l2.take().as_deref() {
+					Some("arbitrum") => Some(L2::Arbitrum),
+					Some("optimism") => Some(L2::Optimism),
+					Some(l2) => return Err(ParseError::InvalidL2(l2.to_owned())),
+					None => None,
+				},
+				export_csv: v.export_csv.take(),
+				deployer: v.deployer.take(),
+				constructor_args: match (v.constructor_args.take(), &v.recipient) {
+					(Some(_), Some(_)) => {
+						return Err(ParseError::ConflictingFlags(
+							"--constructor-args",
+							"--recipient",
+						))
+					}
+					(Some(args), None) => Some(
+						serde_json::from_str(&args)
+							.map_err(|_| ParseError::InvalidConstructorArgs(args))?,
+					),
+					(None, _) => None,
+				},
+				recipient: v.recipient.take(),
+				strict_cid: v.strict_cid,
+				simulate_only: v.simulate_only,
+				at_block: match v.at_block.take().as_deref() {
+					Some("latest") => Some(BlockNumber::Latest),
+					Some("pending") => Some(BlockNumber::Pending),
+					Some(block) => Some(BlockNumber::Number(
+						block
+							.parse::<u64>()
+							.map_err(|_| ParseError::InvalidBlock(block.to_owned()))?
+							.into(),
+					)),
+					None => None,
 				},
-				ipfs: {
-					IpfsClient::from_str(
-						v.ipfs_uri
-							.as_deref()
-							.unwrap_or_else(|| DEFAULT_IPFS_GATEWAY),
-					)
-					.map_err(|e| ParseError::MiscError(Box::new(e)))?
+				gateway: gateway_url(v.gateway.take())?,
+				access_list: v.access_list.take(),
+				confirmation: match (
+					parse_limit("--confirmations", v.confirmations.take())?,
+					parse_limit("--confirm-seconds", v.confirm_seconds.take())?,
+				) {
+					(Some(_), Some(_)) => {
+						return Err(ParseError::ConflictingFlags(
+							"--confirmations",
+							"--confirm-seconds",
+						))
+					}
+					(Some(confirmations), None) => Some(Confirmation::Blocks(confirmations)),
+					(None, Some(secs)) => Some(Confirmation::Elapsed(Duration::from_secs(secs))),
+					(None, None) => None,
 				},
+				output: output_format(v.output.take())?,
 			}))),
 			Some(CommandBuilder::List) => Ok(Self::List(ListContext {
+				artifact: v.take_artifact()?,
 				private_key: v.private_key.ok_or(ParseError::MissingPrivateKey)?,
-				eth_uri: v.eth_uri.ok_or(ParseError::MissingRpcUrlETH)?,
+				eth_uri: rpc_url(v.eth_uri)?,
+				eth_chain_id: v
+					.eth_chain_id
+					.ok_or(ParseError::MissingChainId)?
+					.parse()
+					.map_err(|_| ParseError::MissingChainId)?,
+				quiet: v.quiet,
+				checkpoint: v.checkpoint,
+				scan_concurrency: match parse_limit("--scan-concurrency", v.scan_concurrency)? {
+					Some(0) => {
+						return Err(ParseError::InvalidLimit(
+							"--scan-concurrency",
+							"0".to_owned(),
+						))
+					}
+					Some(n) => n,
+					None => DEFAULT_SCAN_CONCURRENCY,
+				},
+				limit: parse_limit("--limit", v.limit)?,
+				offset: parse_limit("--offset", v.offset)?.unwrap_or_default(),
+				list_timeout: v
+					.list_timeout
+					.map(|secs| {
+						secs.parse()
+							.map(Duration::from_secs)
+							.map_err(|_| ParseError::InvalidTimeout(secs))
+					})
+					.transpose()?,
+				strict: v.strict,
+				output: output_format(v.output)?,
+				stream: v.stream,
+				resolve_metadata: v.resolve_metadata,
+				export_csv: v.export_csv,
+			})),
+			Some(CommandBuilder::Doctor) => Ok(Self::Doctor(DoctorContext {
+				ipfs: v.ipfs_client()?,
+				artifact: v.take_artifact()?,
+				eth_uri: rpc_url(v.eth_uri)?,
 				eth_chain_id: v
 					.eth_chain_id
 					.ok_or(ParseError::MissingChainId)?
 					.parse()
 					.map_err(|_| ParseError::MissingChainId)?,
-				contracts_dir: v.contracts_dir.ok_or(ParseError::MissingContractsSrc)?,
+			})),
+			Some(CommandBuilder::Modules) => Ok(Self::Modules(ModulesContext {
+				ipfs: v.ipfs_client()?,
+				output: output_format(v.output.take())?,
+				gateway: gateway_url(v.gateway.take())?,
+				target: match v.module_paths.first() {
+					Some(cid) if is_cid(cid) => ModulesTarget::Metadata(cid.clone()),
+					Some(addr) => ModulesTarget::Contract {
+						address: Address::from_str(addr)
+							.map_err(|_| ParseError::InvalidModulesTarget(addr.clone()))?,
+						artifact: v.take_artifact()?,
+						eth_uri: rpc_url(v.eth_uri)?,
+					},
+					None => return Err(ParseError::MissingModulesTarget),
+				},
+			})),
+			Some(CommandBuilder::Abi) => Ok(Self::Abi(AbiContext {
+				artifact: v.take_artifact()?,
+			})),
+			Some(CommandBuilder::Version) => Ok(Self::Version(VersionContext {
+				output: output_format(v.output.take())?,
+				artifact: match v.contracts_dir {
+					Some(_) => Some(v.take_artifact()?),
+					None => None,
+				},
 			})),
 			None => Err(ParseError::MissingCommand),
+		}?;
+
+		// Spawn an IPFS node if the user didn't specify a host. A bad flag
+		// found after this would leave the node running, holding the repo's
+		// lock
+		if needs_ipfs_node {
+			if let Some(ctx) = cmd.upload_context_mut() {
+				ctx.ipfs_handle = Some(spawn_ipfs(ctx.keep_ipfs_alive)?);
+			}
 		}
+
+		Ok(cmd)
 	}
 }
 
 enum CommandBuilder {
 	New,
 	List,
+	Doctor,
+	Modules,
+	Abi,
+	Version,
 }
 
 /// An error encountered while parsing CLI args.
@@ -185,8 +1186,36 @@ pub enum ParseError {
 	MissingCommand,
 	MissingPrivateKey,
 	MissingRpcUrlETH,
+	InvalidRpcUrl(String),
+	InvalidGateway(String),
 	MissingContractsSrc,
 	MissingChainId,
+	InvalidMetadataCid(String),
+	InvalidDescriptionFile(String, Box<dyn StdError>),
+	InvalidSupply(String),
+	InvalidTokenAmount(String),
+	InvalidDecimals(String),
+	SupplyOverflow(String, u32),
+	InvalidTimeout(String),
+	InvalidLimit(&'static str, String),
+	InvalidSchemaVersion(String),
+	InvalidArtifactFormat(String),
+	InvalidL2(String),
+	InvalidBlock(String),
+	InvalidConstructorArgs(String),
+	UnknownChain(String),
+	InvalidOutputFormat(String),
+	MissingModulesTarget,
+	InvalidModulesTarget(String),
+	UnknownFlag(String),
+	MissingFlagValue(String),
+	ConflictingFlags(&'static str, &'static str),
+	RequiredFlag(&'static str, &'static str),
+	TooManyModules(usize, usize),
+	ModuleCollision(String, String, String),
+	ModulesTooLarge(u64, u64),
+	ModuleTooLarge(String, u64, u64),
+	IpfsDaemonExited(ExitStatus),
 	MiscError(Box<dyn StdError>),
 }
 
@@ -200,79 +1229,469 @@ impl fmt::Display for ParseError {
 				PRIVATE_KEY_ARG
 			),
 			Self::MissingRpcUrlETH => write!(fmt, "config error: command requires a --eth-rpc-uri"),
+			Self::InvalidRpcUrl(uri) => write!(
+				fmt,
+				"config error: --eth-rpc-uri {uri} is not an http or https URL"
+			),
+			Self::InvalidGateway(gateway) => {
+				write!(fmt, "config error: --gateway {gateway} is not an http or https URL")
+			}
 			Self::MissingContractsSrc => {
 				write!(fmt, "config error: command requires a --contracts-dir")
 			}
 			Self::MiscError(e) => write!(fmt, "error: {e}"),
 			Self::MissingChainId => write!(fmt, "config error: command requires a --eth-chain-id"),
+			Self::InvalidMetadataCid(cid) => {
+				write!(fmt, "config error: --metadata-cid {cid} is not a valid CID")
+			}
+			Self::InvalidDescriptionFile(path, e) => {
+				write!(fmt, "config error: --description-file {path} can't be used: {e}")
+			}
+			Self::InvalidSupply(supply) => {
+				write!(
+					fmt,
+					"config error: supply {supply} is not a decimal integer"
+				)
+			}
+			Self::InvalidTokenAmount(amount) => write!(
+				fmt,
+				"config error: --supply-tokens {amount} is not a decimal number of tokens"
+			),
+			Self::InvalidDecimals(decimals) => {
+				write!(fmt, "config error: --decimals {decimals} is not a whole number")
+			}
+			Self::SupplyOverflow(amount, decimals) => write!(
+				fmt,
+				"config error: {amount} tokens with {decimals} decimals doesn't fit in a uint256"
+			),
+			Self::InvalidLimit(flag, limit) => {
+				write!(fmt, "config error: {flag} {limit} is not a valid limit")
+			}
+			Self::InvalidSchemaVersion(version) => write!(
+				fmt,
+				"config error: --metadata-schema-version {version} is not a whole number"
+			),
+			Self::InvalidArtifactFormat(format) => write!(
+				fmt,
+				"config error: --artifact-format {format} is not combined, foundry, or hardhat"
+			),
+			Self::InvalidL2(l2) => {
+				write!(fmt, "config error: --l2 {l2} is not arbitrum or optimism")
+			}
+			Self::InvalidBlock(block) => write!(
+				fmt,
+				"config error: --at-block {block} is not a block number, latest, or pending"
+			),
+			Self::InvalidConstructorArgs(args) => {
+				write!(fmt, "parse error: --constructor-args {args} is not a JSON array")
+			}
+			Self::UnknownChain(name) => write!(
+				fmt,
+				"config error: --chain {name} is not one of {}",
+				CHAINS.map(|chain| chain.name).join(", ")
+			),
+			Self::InvalidOutputFormat(format) => {
+				write!(fmt, "config error: --output {format} is not text or json")
+			}
+			Self::MissingModulesTarget => write!(
+				fmt,
+				"parse error: command requires the address of a DAO or the CID of its metadata"
+			),
+			Self::InvalidModulesTarget(target) => write!(
+				fmt,
+				"parse error: {target} is not the address of a DAO or the CID of its metadata"
+			),
+			Self::UnknownFlag(flag) => write!(fmt, "parse error: unknown flag {flag}"),
+			Self::MissingFlagValue(flag) => {
+				write!(fmt, "parse error: {flag} requires a value")
+			}
+			Self::ConflictingFlags(a, b) => {
+				write!(fmt, "config error: {a} and {b} can't both be specified")
+			}
+			Self::RequiredFlag(a, b) => write!(fmt, "config error: {a} requires a {b}"),
+			Self::TooManyModules(count, max) => write!(
+				fmt,
+				"config error: {count} modules were specified, but at most {max} are allowed by --max-modules"
+			),
+			Self::ModuleCollision(name, first, second) => write!(
+				fmt,
+				"config error: module {name} is in both --modules-dir {first} and {second}"
+			),
+			Self::ModulesTooLarge(total, max) => write!(
+				fmt,
+				"config error: the modules total {total} bytes, but at most {max} are allowed by --max-total-module-bytes"
+			),
+			Self::ModuleTooLarge(path, size, max) => write!(
+				fmt,
+				"config error: {path} is {size} bytes, but at most {max} are allowed by --max-module-bytes"
+			),
+			Self::InvalidTimeout(secs) => {
+				write!(
+					fmt,
+					"config error: timeout {secs} is not a number of seconds"
+				)
+			}
+			Self::IpfsDaemonExited(status) => write!(
+				fmt,
+				"error: the IPFS daemon exited ({status}) before its API was ready; is another daemon using the repo?"
+			),
 		}
 	}
 }
 
 impl StdError for ParseError {}
 
+/// Where an IPFS daemon left running by --keep-ipfs-alive writes its logs.
+pub(crate) fn ipfs_log_path() -> PathBuf {
+	env::temp_dir().join(IPFS_LOG_FILE)
+}
+
+/// Starts an IPFS daemon in the background, returning once its API is ready.
+fn spawn_ipfs(keep_alive: bool) -> Result<Child, ParseError> {
+	log::debug!("starting IPFS daemon");
+
+	let misc = |e: io::Error| ParseError::MiscError(Box::new(e));
+
+	// A daemon that outlives daowiz can't write to pipes that daowiz reads,
+	// since it would be killed by SIGPIPE once daowiz exits
+	if keep_alive {
+		let path = ipfs_log_path();
+		let log = File::create(&path).map_err(misc)?;
+
+		let mut cmd = ProcCommand::new("ipfs");
+		cmd.arg("daemon")
+			.stdin(Stdio::null())
+			.stdout(log.try_clone().map_err(misc)?)
+			.stderr(log);
+
+		// Keep the daemon out of daowiz's process group, so that a ^C at the
+		// terminal doesn't stop it too
+		#[cfg(unix)]
+		std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+		let mut child = cmd.spawn().map_err(misc)?;
+
+		loop {
+			if fs::read_to_string(&path)
+				.map_err(misc)?
+				.contains(IPFS_READY_LINE)
+			{
+				return Ok(child);
+			}
+
+			if let Some(status) = child.try_wait().map_err(misc)? {
+				return Err(ParseError::IpfsDaemonExited(status));
+			}
+
+			thread::sleep(IPFS_POLL_INTERVAL);
+		}
+	}
+
+	let mut child = ProcCommand::new("ipfs")
+		.arg("daemon")
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.map_err(misc)?;
+
+	let (tx, rx) = mpsc::channel();
+	let out = child.stdout.take().expect("daemon's stdout is piped");
+
+	thread::spawn(move || {
+		let mut lines = BufReader::new(out).lines().map_while(Result::ok);
+
+		for l in lines.by_ref() {
+			debug!("{l}");
+
+			if l.contains(IPFS_READY_LINE) {
+				let _ = tx.send(());
+				break;
+			}
+		}
+
+		// Keep draining the daemon's output so that it never blocks on a full
+		// pipe. Ends once the daemon exits, which drops the sender if the API
+		// never came up
+		lines.for_each(drop);
+	});
+
+	match rx.recv() {
+		Ok(()) => Ok(child),
+		Err(_) => Err(ParseError::IpfsDaemonExited(child.wait().map_err(misc)?)),
+	}
+}
+
+/// Converts an amount of whole tokens, possibly with a fractional part, to
+/// the token's smallest unit.
+fn token_supply(amount: &str, decimals: u32) -> Result<U256, ParseError> {
+	let overflow = || ParseError::SupplyOverflow(amount.to_owned(), decimals);
+
+	let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+	if whole.is_empty()
+		|| frac.len() > decimals as usize
+		|| !whole
+			.chars()
+			.chain(frac.chars())
+			.all(|c| c.is_ascii_digit())
+	{
+		return Err(ParseError::InvalidTokenAmount(amount.to_owned()));
+	}
+
+	// Pad the fractional part out to the token's decimals, so that both
+	// parts are in the smallest unit
+	let unit = U256::from(10)
+		.checked_pow(decimals.into())
+		.ok_or_else(overflow)?;
+	let frac = match format!("{frac:0<width$}", width = decimals as usize) {
+		frac if frac.is_empty() => U256::zero(),
+		frac => U256::from_dec_str(&frac).map_err(|_| overflow())?,
+	};
+
+	U256::from_dec_str(whole)
+		.map_err(|_| overflow())?
+		.checked_mul(unit)
+		.and_then(|whole| whole.checked_add(frac))
+		.ok_or_else(overflow)
+}
+
+/// Parses the value of --output, which defaults to text.
+fn output_format(format: Option<String>) -> Result<OutputFormat, ParseError> {
+	match format {
+		Some(format) if format == "json" => Ok(OutputFormat::Json),
+		Some(format) if format != "text" => Err(ParseError::InvalidOutputFormat(format)),
+		_ => Ok(OutputFormat::Text),
+	}
+}
+
+/// Parses the value of a flag limiting the size of the inputs, if it was
+/// specified.
+fn parse_limit<T: FromStr>(
+	flag: &'static str,
+	limit: Option<String>,
+) -> Result<Option<T>, ParseError> {
+	limit
+		.map(|limit| {
+			limit
+				.parse()
+				.map_err(|_| ParseError::InvalidLimit(flag, limit))
+		})
+		.transpose()
+}
+
+/// Parses the value of --gateway, which must be an http(s) URL.
+fn gateway_url(gateway: Option<String>) -> Result<String, ParseError> {
+	match gateway {
+		Some(gateway) => match Url::parse(&gateway) {
+			Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(gateway),
+			_ => Err(ParseError::InvalidGateway(gateway)),
+		},
+		None => Ok(DEFAULT_GATEWAY.to_owned()),
+	}
+}
+
+/// Unwraps the RPC URL, checking that it is an http(s) or ws(s) URL.
+fn rpc_url(uri: Option<String>) -> Result<String, ParseError> {
+	let uri = uri.ok_or(ParseError::MissingRpcUrlETH)?;
+
+	match Url::parse(&uri) {
+		Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(uri),
+		_ => Err(ParseError::InvalidRpcUrl(uri)),
+	}
+}
+
+/// Checks that the string is a well-formed CID: either a base58-encoded CIDv0,
+/// or a multibase-encoded CIDv1.
+fn is_cid(s: &str) -> bool {
+	// CIDv0's are bare sha2-256 multihashes
+	if s.starts_with("Qm") {
+		return matches!(
+			Base::Base58Btc.decode(s),
+			Ok(bytes) if bytes.len() == 34 && bytes[..2] == [0x12, 0x20]
+		);
+	}
+
+	// CIDv1's start with their version, followed by a codec and a multihash
+	matches!(multibase::decode(s), Ok((_, bytes)) if bytes.len() > 2 && bytes[0] == 0x01)
+}
+
+/// Parses the command and flags in the args following the program name. Flags
+/// that take a value accept it as the next arg, or as --flag=value. Args that
+/// aren't flags are recorded as the paths of modules, without opening them.
+fn parse_flags(mut tokens: impl Iterator<Item = String>) -> Result<ContextBuilder, ParseError> {
+	let mut builder = ContextBuilder {
+		// new, ls, or doctor should be the first arg
+		cmd: tokens.next().and_then(|cmd| match cmd.as_str() {
+			"new" => Some(CommandBuilder::New),
+			"list" => Some(CommandBuilder::List),
+			"doctor" => Some(CommandBuilder::Doctor),
+			"modules" => Some(CommandBuilder::Modules),
+			"abi" => Some(CommandBuilder::Abi),
+			"version" => Some(CommandBuilder::Version),
+			_ => None,
+		}),
+		..Default::default()
+	};
+
+	while let Some(token) = tokens.next() {
+		let (flag, inline) = match token.split_once('=') {
+			Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+			_ => (token.as_str(), None),
+		};
+
+		// Flags missing their value are caught, rather than leaving the
+		// option unset
+		let mut value = || {
+			inline
+				.map(str::to_owned)
+				.or_else(|| tokens.next())
+				.ok_or_else(|| ParseError::MissingFlagValue(flag.to_owned()))
+		};
+
+		match flag {
+			"--eth-rpc-uri" => builder.eth_uri = Some(value()?),
+			"--eth-chain-id" => builder.eth_chain_id = Some(value()?),
+			"--chain" => builder.chain = Some(value()?),
+			"--ipfs-rpc-uri" => builder.ipfs_uri = Some(value()?),
+			"--gateway" => builder.gateway = Some(value()?),
+			"--contracts-dir" => builder.contracts_dir = Some(value()?),
+			"--artifact-path" => builder.artifact_path = Some(value()?),
+			"--bytecode-file" => builder.bytecode_file = Some(value()?),
+			"--artifact-format" => builder.artifact_format = Some(value()?),
+			"--upload-cache" => builder.upload_cache = Some(value()?),
+			"--keep-ipfs-alive" if inline.is_none() => builder.keep_ipfs_alive = true,
+			"--replication-check" => builder.replication_check = Some(value()?),
+			"--require-replication" if inline.is_none() => builder.require_replication = true,
+			"--check-ipfs-storage" if inline.is_none() => builder.check_ipfs_storage = true,
+			"--print-metadata" if inline.is_none() => builder.print_metadata = true,
+			"--validate-modules" if inline.is_none() => builder.validate_modules = true,
+			"--validate-loaders" if inline.is_none() => builder.validate_loaders = true,
+			"--strict-modules" if inline.is_none() => builder.strict_modules = true,
+			"--require-export" => builder.required_exports.push(value()?),
+			"--strip-wasm" if inline.is_none() => builder.strip_wasm = true,
+			"--keep-wasm-names" if inline.is_none() => builder.keep_wasm_names = true,
+			"--metadata-only" if inline.is_none() => builder.metadata_only = true,
+			"--metadata-cid" => builder.metadata_cid = Some(value()?),
+			"--simulate-only" if inline.is_none() => builder.simulate_only = true,
+			"--at-block" => builder.at_block = Some(value()?),
+			"--name" => builder.name = Some(value()?),
+			"--symbol" => builder.symbol = Some(value()?),
+			"--description" => builder.description = Some(value()?),
+			"--description-file" => builder.description_file = Some(value()?),
+			"--icon" => builder.icon = Some(value()?),
+			"--compress-description" if inline.is_none() => builder.compress_description = true,
+			"--metadata-schema-version" => builder.metadata_schema_version = Some(value()?),
+			"--supply" => builder.supply = Some(value()?),
+			"--supply-tokens" => builder.supply_tokens = Some(value()?),
+			"--decimals" => builder.decimals = Some(value()?),
+			"--config" => builder.config = Some(value()?),
+			"--state-file" => builder.state_file = Some(value()?),
+			"--deploy-timeout" => builder.deploy_timeout = Some(value()?),
+			"--confirmations" => builder.confirmations = Some(value()?),
+			"--confirm-seconds" => builder.confirm_seconds = Some(value()?),
+			"--plan" if inline.is_none() => builder.plan = true,
+			"--l2" => builder.l2 = Some(value()?),
+			"--quiet" if inline.is_none() => builder.quiet = true,
+			"--checkpoint" => builder.checkpoint = Some(value()?),
+			"--scan-concurrency" => builder.scan_concurrency = Some(value()?),
+			"--limit" => builder.limit = Some(value()?),
+			"--offset" => builder.offset = Some(value()?),
+			"--list-timeout" => builder.list_timeout = Some(value()?),
+			"--strict" if inline.is_none() => builder.strict = true,
+			"--max-modules" => builder.max_modules = Some(value()?),
+			"--max-total-module-bytes" => builder.max_total_module_bytes = Some(value()?),
+			"--max-module-bytes" => builder.max_module_bytes = Some(value()?),
+			"--output" => builder.output = Some(value()?),
+			"--resolve-metadata" if inline.is_none() => builder.resolve_metadata = true,
+			"--stream" if inline.is_none() => builder.stream = true,
+			"--export-csv" => builder.export_csv = Some(value()?),
+			"--deployer" => builder.deployer = Some(value()?),
+			"--recipient" => builder.recipient = Some(value()?),
+			"--constructor-args" => builder.constructor_args = Some(value()?),
+			"--strict-cid" if inline.is_none() => builder.strict_cid = true,
+			"--access-list" => builder.access_list = Some(value()?),
+			"--modules-dir" => builder.modules_dirs.push(value()?),
+			flag if flag.starts_with("--") => return Err(ParseError::UnknownFlag(token.clone())),
+			_ => builder.module_paths.push(token.clone()),
+		}
+	}
+
+	Ok(builder)
+}
+
+/// Fills in the details that flags leave unspecified, from the environment
+/// read through the lookup, then from the config's defaults, and then from
+/// the --chain preset.
+fn fill_defaults(
+	builder: &mut ContextBuilder,
+	Config { defaults }: Config,
+	env: impl Fn(&str) -> Option<String>,
+) -> Result<(), ParseError> {
+	builder.eth_uri = builder.eth_uri.take().or_else(|| env(ETH_RPC_URI_ARG));
+	builder.eth_chain_id = builder
+		.eth_chain_id
+		.take()
+		.or_else(|| env(ETH_CHAIN_ID_ARG));
+	builder.contracts_dir = builder
+		.contracts_dir
+		.take()
+		.or_else(|| env(CONTRACTS_DIR_ARG));
+
+	// Private key is required for all commands
+	builder.private_key = env(PRIVATE_KEY_ARG);
+
+	let Defaults {
+		name,
+		symbol,
+		description,
+		supply,
+	} = defaults;
+	builder.name = builder.name.take().or(name);
+	builder.symbol = builder.symbol.take().or(symbol);
+	builder.description = builder.description.take().or(description);
+	builder.supply = builder.supply.take().or(supply);
+
+	if let Some(name) = builder.chain.take() {
+		let chain = CHAINS
+			.iter()
+			.find(|chain| chain.name == name)
+			.ok_or(ParseError::UnknownChain(name))?;
+
+		builder.eth_uri = builder
+			.eth_uri
+			.take()
+			.or_else(|| Some(chain.rpc_uri.to_owned()));
+		builder.eth_chain_id = builder
+			.eth_chain_id
+			.take()
+			.or_else(|| Some(chain.id.to_string()));
+		builder.l2 = builder.l2.take().or_else(|| chain.l2.map(str::to_owned));
+	}
+
+	Ok(())
+}
+
 /// Gets the configuration of the command-line client from the command-line
 /// args.
 impl TryFrom<Args> for Context {
 	type Error = ParseError;
 
-	fn try_from(mut v: Args) -> Result<Self, Self::Error> {
-		let mut builder = ContextBuilder {
-			// new, or ls should be the first arg after the program name, which
-			// is already extracted
-			cmd: v.nth(1).and_then(|cmd| match cmd.as_str() {
-				"new" => Some(CommandBuilder::New),
-				"list" => Some(CommandBuilder::List),
-				_ => None,
-			}),
-			..Default::default()
-		};
+	fn try_from(v: Args) -> Result<Self, Self::Error> {
+		// Skip the program name
+		let mut builder = parse_flags(v.skip(1))?;
 
-		// Parse flags
-		for (k, v) in v.into_iter().tuples() {
-			match k.as_str() {
-				"--eth-rpc-uri" => builder.eth_uri = Some(v),
-				"--eth-chain-id" => builder.eth_chain_id = Some(v),
-				"--ipfs-rpc-uri" => builder.ipfs_uri = Some(v),
-				"--contracts-dir" => builder.contracts_dir = Some(v),
-
-				// Open non-flag args that end with .wasm as modules
-				_ => {
-					for fname in [k, v] {
-						// Get slot storing js loader and wasm module
-						let stripped = fname
-							.trim_end_matches(".wasm")
-							.trim_end_matches(".js")
-							.trim_end_matches("_bg");
-
-						if let Ok(f) = OpenOptions::new().read(true).open(&fname) {
-							// Set the slot to the default
-							if !builder.files.contains_key(stripped) {
-								builder.files.insert(stripped.to_owned(), (None, None));
-							}
-
-							// Sort encountered files by loader, or module type
-							if fname.ends_with(".wasm") {
-								builder
-									.files
-									.get_mut(stripped)
-									.ok_or(ParseError::MissingContractsSrc)?
-									.1 = Some(f);
-							} else if fname.ends_with(".js") {
-								builder
-									.files
-									.get_mut(stripped)
-									.ok_or(ParseError::MissingContractsSrc)?
-									.0 = Some(f);
-							}
-						}
-					}
-				}
-			}
+		// Only new deploys the modules specified
+		if matches!(builder.cmd, Some(CommandBuilder::New)) {
+			builder.discover_modules()?;
+			builder.open_modules()?;
 		}
 
-		// Private key is required for all commands
-		builder.private_key = env::var(PRIVATE_KEY_ARG).ok();
+		let config = builder
+			.config
+			.take()
+			.map(|path| Config::load(&path))
+			.transpose()?
+			.unwrap_or_default();
+		fill_defaults(&mut builder, config, |key| env::var(key).ok())?;
 
 		Ok(Context {
 			cmd: Command::try_from(builder)?,
@@ -296,3 +1715,602 @@ pub fn usage(args: &mut Args) {
 
 	process::exit(0x0100);
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::temp_dir;
+	use std::path::Path;
+
+	fn parse(args: &[&str]) -> Result<ContextBuilder, ParseError> {
+		parse_flags(args.iter().map(|arg| arg.to_string()))
+	}
+
+	/// Flags that take a value, and how to read it back from the builder.
+	type ValueFlag = (&'static str, fn(&ContextBuilder) -> Option<&String>);
+
+	const VALUE_FLAGS: &[ValueFlag] = &[
+		("--eth-rpc-uri", |b| b.eth_uri.as_ref()),
+		("--eth-chain-id", |b| b.eth_chain_id.as_ref()),
+		("--chain", |b| b.chain.as_ref()),
+		("--ipfs-rpc-uri", |b| b.ipfs_uri.as_ref()),
+		("--gateway", |b| b.gateway.as_ref()),
+		("--contracts-dir", |b| b.contracts_dir.as_ref()),
+		("--artifact-path", |b| b.artifact_path.as_ref()),
+		("--bytecode-file", |b| b.bytecode_file.as_ref()),
+		("--artifact-format", |b| b.artifact_format.as_ref()),
+		("--upload-cache", |b| b.upload_cache.as_ref()),
+		("--replication-check", |b| b.replication_check.as_ref()),
+		("--require-export", |b| b.required_exports.first()),
+		("--metadata-cid", |b| b.metadata_cid.as_ref()),
+		("--at-block", |b| b.at_block.as_ref()),
+		("--name", |b| b.name.as_ref()),
+		("--symbol", |b| b.symbol.as_ref()),
+		("--description", |b| b.description.as_ref()),
+		("--description-file", |b| b.description_file.as_ref()),
+		("--icon", |b| b.icon.as_ref()),
+		("--metadata-schema-version", |b| {
+			b.metadata_schema_version.as_ref()
+		}),
+		("--supply", |b| b.supply.as_ref()),
+		("--supply-tokens", |b| b.supply_tokens.as_ref()),
+		("--decimals", |b| b.decimals.as_ref()),
+		("--config", |b| b.config.as_ref()),
+		("--state-file", |b| b.state_file.as_ref()),
+		("--deploy-timeout", |b| b.deploy_timeout.as_ref()),
+		("--confirmations", |b| b.confirmations.as_ref()),
+		("--confirm-seconds", |b| b.confirm_seconds.as_ref()),
+		("--l2", |b| b.l2.as_ref()),
+		("--checkpoint", |b| b.checkpoint.as_ref()),
+		("--scan-concurrency", |b| b.scan_concurrency.as_ref()),
+		("--limit", |b| b.limit.as_ref()),
+		("--offset", |b| b.offset.as_ref()),
+		("--list-timeout", |b| b.list_timeout.as_ref()),
+		("--max-modules", |b| b.max_modules.as_ref()),
+		("--max-total-module-bytes", |b| {
+			b.max_total_module_bytes.as_ref()
+		}),
+		("--max-module-bytes", |b| b.max_module_bytes.as_ref()),
+		("--output", |b| b.output.as_ref()),
+		("--export-csv", |b| b.export_csv.as_ref()),
+		("--deployer", |b| b.deployer.as_ref()),
+		("--recipient", |b| b.recipient.as_ref()),
+		("--constructor-args", |b| b.constructor_args.as_ref()),
+		("--access-list", |b| b.access_list.as_ref()),
+		("--modules-dir", |b| b.modules_dirs.first()),
+	];
+
+	/// Flags that stand alone, and how to read them back from the builder.
+	type BoolFlag = (&'static str, fn(&ContextBuilder) -> bool);
+
+	const BOOL_FLAGS: &[BoolFlag] = &[
+		("--keep-ipfs-alive", |b| b.keep_ipfs_alive),
+		("--require-replication", |b| b.require_replication),
+		("--check-ipfs-storage", |b| b.check_ipfs_storage),
+		("--print-metadata", |b| b.print_metadata),
+		("--validate-modules", |b| b.validate_modules),
+		("--validate-loaders", |b| b.validate_loaders),
+		("--strict-modules", |b| b.strict_modules),
+		("--strip-wasm", |b| b.strip_wasm),
+		("--keep-wasm-names", |b| b.keep_wasm_names),
+		("--metadata-only", |b| b.metadata_only),
+		("--simulate-only", |b| b.simulate_only),
+		("--compress-description", |b| b.compress_description),
+		("--plan", |b| b.plan),
+		("--quiet", |b| b.quiet),
+		("--strict", |b| b.strict),
+		("--resolve-metadata", |b| b.resolve_metadata),
+		("--stream", |b| b.stream),
+		("--strict-cid", |b| b.strict_cid),
+	];
+
+	#[test]
+	fn parses_commands() {
+		assert!(matches!(
+			parse(&["new"]).unwrap().cmd,
+			Some(CommandBuilder::New)
+		));
+		assert!(matches!(
+			parse(&["list"]).unwrap().cmd,
+			Some(CommandBuilder::List)
+		));
+		assert!(matches!(
+			parse(&["doctor"]).unwrap().cmd,
+			Some(CommandBuilder::Doctor)
+		));
+		assert!(matches!(
+			parse(&["modules"]).unwrap().cmd,
+			Some(CommandBuilder::Modules)
+		));
+		assert!(matches!(
+			parse(&["abi"]).unwrap().cmd,
+			Some(CommandBuilder::Abi)
+		));
+		assert!(matches!(
+			parse(&["version"]).unwrap().cmd,
+			Some(CommandBuilder::Version)
+		));
+		assert!(parse(&["deploy"]).unwrap().cmd.is_none());
+		assert!(parse(&[]).unwrap().cmd.is_none());
+	}
+
+	#[test]
+	fn parses_value_flags() {
+		for (flag, get) in VALUE_FLAGS {
+			let builder = parse(&["new", flag, "value"]).unwrap();
+			assert_eq!(get(&builder).map(String::as_str), Some("value"), "{flag}");
+		}
+	}
+
+	#[test]
+	fn parses_inline_values() {
+		for (flag, get) in VALUE_FLAGS {
+			let builder = parse(&["new", &format!("{flag}=value")]).unwrap();
+			assert_eq!(get(&builder).map(String::as_str), Some("value"), "{flag}");
+		}
+
+		// Only the first = splits the flag from its value
+		let builder = parse(&["new", "--eth-rpc-uri=http://node?a=b"]).unwrap();
+		assert_eq!(builder.eth_uri.as_deref(), Some("http://node?a=b"));
+
+		// An inline value can be empty
+		let builder = parse(&["new", "--name="]).unwrap();
+		assert_eq!(builder.name.as_deref(), Some(""));
+	}
+
+	#[test]
+	fn parses_bool_flags() {
+		for (flag, get) in BOOL_FLAGS {
+			assert!(!get(&parse(&["new"]).unwrap()), "{flag}");
+			assert!(get(&parse(&["new", flag]).unwrap()), "{flag}");
+		}
+	}
+
+	#[test]
+	fn rejects_values_for_bool_flags() {
+		for (flag, _) in BOOL_FLAGS {
+			let arg = format!("{flag}=true");
+			assert!(
+				matches!(parse(&["new", &arg]), Err(ParseError::UnknownFlag(f)) if f == arg),
+				"{flag}"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_unknown_flags() {
+		assert!(matches!(
+			parse(&["new", "--nmae", "DAO"]),
+			Err(ParseError::UnknownFlag(f)) if f == "--nmae"
+		));
+		assert!(matches!(
+			parse(&["new", "--nmae=DAO"]),
+			Err(ParseError::UnknownFlag(f)) if f == "--nmae=DAO"
+		));
+	}
+
+	#[test]
+	fn rejects_missing_values() {
+		for (flag, _) in VALUE_FLAGS {
+			assert!(
+				matches!(
+					parse(&["new", flag]),
+					Err(ParseError::MissingFlagValue(f)) if f == *flag
+				),
+				"{flag}"
+			);
+		}
+
+		// A flag's value is the next arg, even if it looks like a flag
+		let builder = parse(&["new", "--name", "--plan"]).unwrap();
+		assert_eq!(builder.name.as_deref(), Some("--plan"));
+		assert!(!builder.plan);
+
+		assert!(matches!(
+			parse(&["new", "--name", "DAO", "--symbol"]),
+			Err(ParseError::MissingFlagValue(f)) if f == "--symbol"
+		));
+	}
+
+	#[test]
+	fn collects_module_paths_between_flags() {
+		let builder = parse(&[
+			"new",
+			"a.wasm",
+			"--name",
+			"DAO",
+			"a.js",
+			"--plan",
+			"b.wasm",
+			"--symbol=D",
+			"b.js",
+		])
+		.unwrap();
+
+		assert_eq!(builder.module_paths, ["a.wasm", "a.js", "b.wasm", "b.js"]);
+		assert_eq!(builder.name.as_deref(), Some("DAO"));
+		assert_eq!(builder.symbol.as_deref(), Some("D"));
+		assert!(builder.plan);
+	}
+
+	#[test]
+	fn repeats_list_flags() {
+		let builder = parse(&[
+			"new",
+			"--require-export",
+			"a",
+			"--require-export=b",
+			"--modules-dir",
+			"x",
+			"--modules-dir=y",
+		])
+		.unwrap();
+
+		assert_eq!(builder.required_exports, ["a", "b"]);
+		assert_eq!(builder.modules_dirs, ["x", "y"]);
+	}
+
+	#[test]
+	fn accepts_http_rpc_urls() {
+		assert!(rpc_url(Some("http://localhost:8545".to_owned())).is_ok());
+		assert!(rpc_url(Some("https://node.example".to_owned())).is_ok());
+
+		// Every command talks to the node over HTTP
+		for uri in [
+			"ws://localhost:8546",
+			"wss://node.example",
+			"localhost:8545",
+		] {
+			assert!(matches!(
+				rpc_url(Some(uri.to_owned())),
+				Err(ParseError::InvalidRpcUrl(u)) if u == uri
+			));
+		}
+		assert!(matches!(rpc_url(None), Err(ParseError::MissingRpcUrlETH)));
+	}
+
+	#[test]
+	fn rejects_bad_schema_versions() {
+		let mut builder = parse(&["new", "--metadata-schema-version", "v2"]).unwrap();
+		assert!(matches!(
+			builder.take_metadata(),
+			Err(ParseError::InvalidSchemaVersion(v)) if v == "v2"
+		));
+
+		let mut builder = parse(&["new", "--metadata-schema-version=2"]).unwrap();
+		assert_eq!(builder.take_metadata().unwrap().schema_version, Some(2));
+	}
+
+	#[test]
+	fn later_flags_take_precedence() {
+		let builder = parse(&["new", "--name", "first", "--name=second"]).unwrap();
+		assert_eq!(builder.name.as_deref(), Some("second"));
+	}
+
+	#[test]
+	fn recognizes_cids() {
+		assert!(is_cid("QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"));
+		assert!(is_cid(
+			"bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua"
+		));
+
+		// Too short, not base58, or not a CID at all
+		assert!(!is_cid("QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5"));
+		assert!(!is_cid("Qm0000000000000000000000000000000000000000000O"));
+		assert!(!is_cid("0x1234"));
+		assert!(!is_cid(""));
+	}
+
+	#[test]
+	fn converts_token_amounts() {
+		assert_eq!(token_supply("1", 18).unwrap(), U256::exp10(18));
+		assert_eq!(token_supply("1.5", 2).unwrap(), U256::from(150));
+		assert_eq!(token_supply("0.01", 2).unwrap(), U256::from(1));
+		assert_eq!(token_supply("42", 0).unwrap(), U256::from(42));
+	}
+
+	#[test]
+	fn converts_zero_token_amounts() {
+		assert_eq!(token_supply("0", 18).unwrap(), U256::zero());
+		assert_eq!(token_supply("0.000", 3).unwrap(), U256::zero());
+	}
+
+	#[test]
+	fn rejects_bad_token_amounts() {
+		for amount in ["", ".5", "1.234", "1e3", "-1", "1.2.3", "one"] {
+			assert!(
+				matches!(
+					token_supply(amount, 2),
+					Err(ParseError::InvalidTokenAmount(a)) if a == amount
+				),
+				"{amount}"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_overflowing_token_amounts() {
+		// 10^78 is past the largest uint256
+		assert!(matches!(
+			token_supply("1", 78),
+			Err(ParseError::SupplyOverflow(_, 78))
+		));
+		assert!(matches!(
+			token_supply(&"9".repeat(78), 0),
+			Err(ParseError::SupplyOverflow(_, 0))
+		));
+		assert!(matches!(
+			token_supply(&"2".repeat(60), 18),
+			Err(ParseError::SupplyOverflow(_, 18))
+		));
+	}
+
+	/// Gets the value of the variable in a fake environment.
+	fn env_with<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+		|key| {
+			vars.iter()
+				.find(|(k, _)| *k == key)
+				.map(|(_, v)| v.to_string())
+		}
+	}
+
+	fn config_with_name(name: &str) -> Config {
+		Config {
+			defaults: Defaults {
+				name: Some(name.to_owned()),
+				..Default::default()
+			},
+		}
+	}
+
+	#[test]
+	fn fills_defaults_by_precedence() {
+		let env = [
+			(ETH_RPC_URI_ARG, "http://env:8545"),
+			(ETH_CHAIN_ID_ARG, "31337"),
+			(PRIVATE_KEY_ARG, "key"),
+		];
+
+		// Flags beat the environment, which beats the preset
+		let mut builder = parse(&[
+			"new",
+			"--chain",
+			"arbitrum",
+			"--eth-rpc-uri",
+			"http://flag:8545",
+		])
+		.unwrap();
+		fill_defaults(&mut builder, Config::default(), env_with(&env)).unwrap();
+		assert_eq!(builder.eth_uri.as_deref(), Some("http://flag:8545"));
+		assert_eq!(builder.eth_chain_id.as_deref(), Some("31337"));
+		assert_eq!(builder.l2.as_deref(), Some("arbitrum"));
+		assert_eq!(builder.private_key.as_deref(), Some("key"));
+
+		// Flags beat the config, which fills in what's left
+		let mut builder = parse(&["new", "--symbol", "VIS"]).unwrap();
+		fill_defaults(&mut builder, config_with_name("Vision DAO"), env_with(&[])).unwrap();
+		assert_eq!(builder.name.as_deref(), Some("Vision DAO"));
+		assert_eq!(builder.symbol.as_deref(), Some("VIS"));
+
+		let mut builder = parse(&["new", "--name", "Flag DAO"]).unwrap();
+		fill_defaults(&mut builder, config_with_name("Vision DAO"), env_with(&[])).unwrap();
+		assert_eq!(builder.name.as_deref(), Some("Flag DAO"));
+	}
+
+	#[test]
+	fn falls_back_on_chain_presets() {
+		let mut builder = parse(&["new", "--chain", "sepolia"]).unwrap();
+		fill_defaults(&mut builder, Config::default(), env_with(&[])).unwrap();
+
+		assert_eq!(
+			builder.eth_uri.as_deref(),
+			Some("https://ethereum-sepolia-rpc.publicnode.com")
+		);
+		assert_eq!(builder.eth_chain_id.as_deref(), Some("11155111"));
+		assert!(builder.l2.is_none());
+		assert!(builder.private_key.is_none());
+	}
+
+	#[test]
+	fn rejects_unknown_chains() {
+		let mut builder = parse(&["new", "--chain", "goerli"]).unwrap();
+
+		assert!(matches!(
+			fill_defaults(&mut builder, Config::default(), env_with(&[])),
+			Err(ParseError::UnknownChain(name)) if name == "goerli"
+		));
+	}
+
+	/// Writes a module of the name to the directory, with a loader and wasm
+	/// module of the sizes.
+	fn write_module(dir: &Path, name: &str, loader_bytes: usize, module_bytes: usize) {
+		fs::write(dir.join(format!("{name}.js")), vec![b' '; loader_bytes]).unwrap();
+		fs::write(dir.join(format!("{name}.wasm")), vec![0; module_bytes]).unwrap();
+	}
+
+	/// Parses the flags of a new deployment of the modules in the directory,
+	/// and opens its modules.
+	fn open_modules_in(dir: &Path, flags: &[&str]) -> Result<ContextBuilder, ParseError> {
+		let dir = dir.display().to_string();
+		let mut builder = parse(&[&["new", "--modules-dir", &dir], flags].concat())?;
+		builder.discover_modules()?;
+		builder.open_modules()?;
+
+		Ok(builder)
+	}
+
+	#[test]
+	fn limits_module_counts() {
+		let dir = temp_dir("max-modules");
+		write_module(&dir, "a", 1, 1);
+		write_module(&dir, "b", 1, 1);
+
+		let mut at_limit = open_modules_in(&dir, &["--max-modules", "2"]).unwrap();
+		assert_eq!(at_limit.take_metadata().unwrap().modules.len(), 2);
+
+		let mut over_limit = open_modules_in(&dir, &["--max-modules", "1"]).unwrap();
+		assert!(matches!(
+			over_limit.take_metadata(),
+			Err(ParseError::TooManyModules(2, 1))
+		));
+	}
+
+	#[test]
+	fn limits_total_module_bytes() {
+		let dir = temp_dir("max-total-module-bytes");
+		write_module(&dir, "a", 3, 4);
+		write_module(&dir, "b", 3, 4);
+
+		let mut at_limit = open_modules_in(&dir, &["--max-total-module-bytes", "14"]).unwrap();
+		assert!(at_limit.take_metadata().is_ok());
+
+		let mut over_limit = open_modules_in(&dir, &["--max-total-module-bytes", "13"]).unwrap();
+		assert!(matches!(
+			over_limit.take_metadata(),
+			Err(ParseError::ModulesTooLarge(14, 13))
+		));
+	}
+
+	#[test]
+	fn rejects_colliding_module_dirs() {
+		let (first, second) = (temp_dir("modules-first"), temp_dir("modules-second"));
+		write_module(&first, "a", 1, 1);
+		write_module(&second, "b", 1, 1);
+
+		// Distinct modules are merged in the order the directories were
+		// given
+		let builder = open_modules_in(&first, &["--modules-dir", &second.display().to_string()]);
+		let names: Vec<_> = builder
+			.unwrap()
+			.files
+			.iter()
+			.map(|(name, _)| Path::new(name).file_name().unwrap().to_owned())
+			.collect();
+		assert_eq!(names, ["a", "b"]);
+
+		write_module(&second, "a", 1, 1);
+		let res = open_modules_in(&first, &["--modules-dir", &second.display().to_string()]);
+		assert!(matches!(
+			res,
+			Err(ParseError::ModuleCollision(name, a, b))
+				if name == "a" && a == first.display().to_string() && b == second.display().to_string()
+		));
+	}
+
+	#[test]
+	fn limits_module_file_sizes() {
+		let dir = temp_dir("max-module-bytes");
+		write_module(&dir, "a", 3, 4);
+
+		assert!(open_modules_in(&dir, &["--max-module-bytes", "4"]).is_ok());
+
+		let wasm = dir.join("a.wasm").display().to_string();
+		assert!(matches!(
+			open_modules_in(&dir, &["--max-module-bytes", "3"]),
+			Err(ParseError::ModuleTooLarge(path, 4, 3)) if path == wasm
+		));
+	}
+
+	#[test]
+	fn reads_description_files() {
+		let dir = temp_dir("description-file");
+		let path = dir.join("README.md").display().to_string();
+		fs::write(&path, "# Vision DAO\n").unwrap();
+
+		let mut builder = parse(&["new", "--description-file", &path]).unwrap();
+		assert_eq!(
+			builder.take_metadata().unwrap().description,
+			"# Vision DAO\n"
+		);
+
+		fs::write(&path, [0xff, 0xfe]).unwrap();
+		let mut builder = parse(&["new", "--description-file", &path]).unwrap();
+		assert!(matches!(
+			builder.take_metadata(),
+			Err(ParseError::InvalidDescriptionFile(p, _)) if p == path
+		));
+
+		fs::remove_dir_all(&dir).unwrap();
+		let mut builder = parse(&["new", "--description-file", &path]).unwrap();
+		assert!(matches!(
+			builder.take_metadata(),
+			Err(ParseError::InvalidDescriptionFile(p, _)) if p == path
+		));
+	}
+
+	#[test]
+	fn requires_cids_for_constructor_args() {
+		assert!(matches!(
+			new_command(&["--constructor-args", "[]"]),
+			Err(ParseError::RequiredFlag(
+				"--constructor-args",
+				"--metadata-cid"
+			))
+		));
+
+		let cmd = new_command(&["--constructor-args", "[]", "--metadata-cid", CID]);
+		assert!(matches!(
+			cmd,
+			Ok(Command::New(ctx)) if matches!(ctx.metadata, Metadata::Existing(_))
+		));
+	}
+
+	#[test]
+	fn uploads_metadata_only() {
+		let builder = parse(&[
+			"new",
+			"--metadata-only",
+			"--ipfs-rpc-uri",
+			"http://127.0.0.1:5001",
+		])
+		.unwrap();
+
+		// No key or node is needed to only upload the metadata
+		let cmd = Command::try_from(builder).unwrap();
+		assert!(matches!(cmd, Command::Metadata(ctx) if ctx.ipfs_handle.is_none()));
+	}
+
+	const CID: &str = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+
+	/// Parses a `new` command with the flags, along with the configuration
+	/// that every deployment requires.
+	fn new_command(flags: &[&str]) -> Result<Command, ParseError> {
+		let mut builder = parse(&[&["new"], flags].concat()).unwrap();
+		builder.private_key = Some("key".to_owned());
+		builder.eth_uri = Some("http://localhost:8545".to_owned());
+		builder.eth_chain_id = Some("1".to_owned());
+		builder.contracts_dir = Some("contracts".to_owned());
+
+		Command::try_from(builder)
+	}
+
+	#[test]
+	fn deploys_against_existing_metadata() {
+		// Nothing is uploaded, so no node is spawned either
+		assert!(matches!(
+			new_command(&["--metadata-cid", CID]),
+			Ok(Command::New(ctx)) if matches!(&ctx.metadata, Metadata::Existing(cid) if cid == CID)
+		));
+
+		assert!(matches!(
+			new_command(&["--metadata-cid", "bafy"]),
+			Err(ParseError::InvalidMetadataCid(cid)) if cid == "bafy"
+		));
+	}
+
+	#[test]
+	fn accepts_http_gateways() {
+		assert_eq!(gateway_url(None).unwrap(), DEFAULT_GATEWAY);
+		assert_eq!(
+			gateway_url(Some("http://localhost:8080".to_owned())).unwrap(),
+			"http://localhost:8080"
+		);
+
+		for gateway in ["ipfs://gateway", "gateway.example"] {
+			assert!(matches!(
+				gateway_url(Some(gateway.to_owned())),
+				Err(ParseError::InvalidGateway(g)) if g == gateway
+			));
+		}
+	}
+}
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+use std::fs;
+
+use super::cli::ParseError;
+
+/// Settings loaded from the TOML file specified by --config.
+#[derive(Deserialize, Default)]
+pub struct Config {
+	#[serde(default)]
+	pub(crate) defaults: Defaults,
+}
+
+/// Details of new DAO's that are used unless overridden by flags, allowing
+/// teams to establish org-wide defaults.
+#[derive(Deserialize, Default)]
+pub struct Defaults {
+	pub(crate) name: Option<String>,
+	pub(crate) symbol: Option<String>,
+	pub(crate) description: Option<String>,
+
+	// Supplies don't fit in TOML integers, so they are written as decimal
+	// strings
+	pub(crate) supply: Option<String>,
+}
+
+impl Config {
+	/// Reads the config from the TOML file at the path.
+	pub fn load(path: &str) -> Result<Self, ParseError> {
+		let src = fs::read_to_string(path).map_err(|e| ParseError::MiscError(Box::new(e)))?;
+
+		toml::from_str(&src).map_err(|e| ParseError::MiscError(Box::new(e)))
+	}
+}
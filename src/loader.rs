@@ -0,0 +1,119 @@
+use swc_common::{sync::Lrc, FileName, SourceMap, Spanned};
+use swc_ecma_parser::{parse_file_as_module, EsSyntax, Syntax};
+
+use super::{net::error::Error, wasm::ModuleOptions};
+
+/// Runs the enabled checks over the module's JS loader, returning an error
+/// naming the module if any of them fail.
+pub fn check(name: &str, src: &[u8], options: &ModuleOptions) -> Result<(), Error> {
+	if options.validate_loaders {
+		let src = String::from_utf8(src.to_vec()).map_err(|e| {
+			Error::InvalidModule(name.to_owned(), format!("its loader isn't UTF-8: {e}"))
+		})?;
+
+		let exports = parse(name, src).map_err(|e| {
+			Error::InvalidModule(name.to_owned(), format!("its loader doesn't parse: {e}"))
+		})?;
+
+		// A loader that exports nothing has nothing for the DAO to call
+		if !exports {
+			return Err(Error::InvalidModule(
+				name.to_owned(),
+				"its loader doesn't export anything".to_owned(),
+			));
+		}
+
+		log::debug!("validated loader of module {name}");
+	}
+
+	Ok(())
+}
+
+/// Parses the loader as an ES module, getting whether it exports anything, or
+/// describing the first syntax error found along with where it is.
+fn parse(name: &str, src: String) -> Result<bool, String> {
+	let files: Lrc<SourceMap> = Default::default();
+	let file = files.new_source_file(Lrc::new(FileName::Custom(format!("{name}.js"))), src);
+
+	// The parser recovers from some errors, which are still reported
+	let mut recovered = Vec::new();
+	let parsed = parse_file_as_module(
+		&file,
+		Syntax::Es(EsSyntax::default()),
+		Default::default(),
+		None,
+		&mut recovered,
+	);
+
+	match (parsed, recovered.into_iter().next()) {
+		(Err(e), _) | (Ok(_), Some(e)) => {
+			let pos = files.lookup_char_pos(e.span().lo);
+
+			Err(format!(
+				"{}:{}: {}",
+				pos.line,
+				pos.col_display + 1,
+				e.kind().msg()
+			))
+		}
+		(Ok(module), None) => Ok(module
+			.body
+			.iter()
+			.filter_map(|item| item.as_module_decl())
+			.any(|decl| !decl.is_import())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn validating() -> ModuleOptions {
+		ModuleOptions {
+			validate_loaders: true,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn accepts_valid_loaders() {
+		let loader = b"import { helper } from './helper.js';\nexport default async function init() { helper(); }\n";
+
+		assert!(check("valid", loader, &validating()).is_ok());
+		assert!(check("valid", b"export const run = () => {};", &validating()).is_ok());
+	}
+
+	#[test]
+	fn rejects_loaders_with_syntax_errors() {
+		let loader = b"export default function init() {\n\tlet = ;\n}\n";
+
+		assert!(matches!(
+			check("broken", loader, &validating()),
+			Err(Error::InvalidModule(name, e))
+				if name == "broken" && e.starts_with("its loader doesn't parse: 2:")
+		));
+
+		// Nothing is checked unless asked
+		assert!(check("broken", loader, &ModuleOptions::default()).is_ok());
+	}
+
+	#[test]
+	fn rejects_loaders_without_exports() {
+		let loader = b"import init from './init.js';\ninit();\n";
+
+		assert!(matches!(
+			check("silent", loader, &validating()),
+			Err(Error::InvalidModule(name, e))
+				if name == "silent" && e == "its loader doesn't export anything"
+		));
+	}
+
+	#[test]
+	fn rejects_non_utf8_loaders() {
+		assert!(matches!(
+			check("binary", &[0xff, 0xfe], &validating()),
+			Err(Error::InvalidModule(name, e))
+				if name == "binary" && e.starts_with("its loader isn't UTF-8")
+		));
+	}
+}
@@ -1,39 +1,239 @@
 #![feature(iterator_try_collect)]
-#![feature(async_closure)]
-#![feature(let_chains)]
-#![feature(try_blocks)]
 
 mod cli;
+mod config;
+mod loader;
 mod net;
+#[cfg(test)]
+mod test_support;
+mod wasm;
 
 #[macro_use]
 extern crate convertable_errors;
 
+use actix_rt::signal;
 use dotenv::dotenv;
-use net::{contract, error::Error};
-use std::{env, process::Child};
+use futures::{
+	future::{self, Either},
+	TryStreamExt,
+};
+use net::{
+	cid,
+	contract::{self, DeployEvent, DeployState, Deployment},
+	doctor,
+	error::Error,
+};
+use std::{
+	env,
+	io::{self, IsTerminal, Write},
+	process::{self, Child},
+	sync::Mutex,
+};
 
-async fn run_cli(args: env::Args) -> (Option<Child>, Result<(), Error>) {
-	// Will throw an error if not enough args were provided
-	let conf = cli::Context::try_from(args).unwrap();
-	let mut handle = None;
+/// Exit code used when the program is interrupted with Ctrl-C.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+async fn run_cli(cmd: cli::Command, state: &Mutex<DeployState>) -> Result<(), Error> {
+	match cmd {
+		cli::Command::New(ctx) if ctx.simulate_only => {
+			let output = ctx.output;
+			let gateway = ctx.gateway.clone();
+			let simulation = contract::simulate(ctx).await?;
+
+			match output {
+				cli::OutputFormat::Text => {
+					println!("{simulation}");
+					println!(
+						"metadata link: {}",
+						cid::gateway_link(&gateway, &simulation.metadata_cid)
+					);
+				}
+				cli::OutputFormat::Json => {
+					println!("{}", serde_json::to_string_pretty(&simulation)?)
+				}
+			}
+		}
+		cli::Command::New(ctx) => {
+			// Only report milestones to people watching
+			let gateway = ctx.gateway.clone();
+			let report = |event| match event {
+				DeployEvent::MetadataUploaded { cid } => eprintln!(
+					"uploaded metadata: {cid} ({})",
+					cid::gateway_link(&gateway, &cid)
+				),
+				DeployEvent::GasLimitSet { gas_limit } => eprintln!("gas limit: {gas_limit}"),
+				DeployEvent::Broadcast { tx_hash } => eprintln!("sent transaction: {:?}", tx_hash),
+				DeployEvent::Confirmed { address } => eprintln!("confirmed: {:?}", address),
+			};
+			let events: Option<&dyn Fn(DeployEvent)> =
+				io::stderr().is_terminal().then_some(&report);
 
-	match conf.cmd {
-		cli::Command::New(mut ctx) => {
-			handle = ctx.ipfs_handle.take();
+			let output = ctx.output;
+			let deployment = contract::deploy(ctx, state, events).await?;
 
-			let addr = contract::deploy(ctx).await.unwrap();
+			match output {
+				cli::OutputFormat::Text => {
+					if let Some(gas) = &deployment.gas {
+						eprintln!("{gas}");
+					}
+					if let Some(recorded) = &deployment.recorded {
+						eprintln!("{recorded}");
+					}
+
+					// No need to print extra output
+					println!("0x{}", hex::encode(deployment.address.0));
+				}
+				cli::OutputFormat::Json => {
+					println!("{}", serde_json::to_string_pretty(&deployment)?)
+				}
+			}
+		}
+		cli::Command::Metadata(ctx) => {
+			println!("{}", contract::publish(*ctx).await?.cid_string);
+		}
+		cli::Command::List(ctx) if ctx.stream => {
+			// Each contract is printed on its own line as soon as it's found
+			let output = ctx.output;
+			let mut deployments = Box::pin(contract::list(ctx));
 
-			// No need to print extra output
-			println!("0x{}", hex::encode(&addr.0));
+			while let Some(deployment) = deployments.try_next().await? {
+				match output {
+					cli::OutputFormat::Text => print_deployment(&deployment),
+					cli::OutputFormat::Json => {
+						println!("{}", serde_json::to_string(&deployment)?)
+					}
+				}
+			}
 		}
 		cli::Command::List(ctx) => {
-			// Print out each deployed contract's address on a separate line
-			println!("{}", contract::list(ctx).await.unwrap().join("\n"));
+			let output = ctx.output;
+			let deployed: Vec<Deployment> = contract::list(ctx).try_collect().await?;
+
+			match output {
+				cli::OutputFormat::Text => deployed.iter().for_each(print_deployment),
+				cli::OutputFormat::Json => {
+					println!("{}", serde_json::to_string_pretty(&deployed)?)
+				}
+			}
+		}
+		cli::Command::Doctor(ctx) => {
+			let mut healthy = true;
+
+			for doctor::Check { name, result } in doctor::diagnose(ctx).await {
+				match result {
+					Ok(msg) => println!("pass {name}: {msg}"),
+					Err(e) => {
+						println!("FAIL {name}: {e}");
+						healthy = false;
+					}
+				}
+			}
+
+			if !healthy {
+				process::exit(1);
+			}
+		}
+		cli::Command::Modules(ctx) => {
+			let output = ctx.output;
+			let gateway = ctx.gateway.clone();
+			let modules = contract::modules(ctx).await?;
+
+			match output {
+				cli::OutputFormat::Text => {
+					for (i, m) in modules.iter().enumerate() {
+						let label = match (&m.name, &m.version) {
+							(Some(name), Some(version)) => format!(" ({name} {version})"),
+							(Some(name), None) => format!(" ({name})"),
+							_ => String::new(),
+						};
+
+						println!(
+							"{i}{label}: loader {}, module {}",
+							cid::gateway_link(&gateway, &m.loader),
+							cid::gateway_link(&gateway, &m.module)
+						);
+					}
+				}
+				cli::OutputFormat::Json => {
+					println!("{}", serde_json::to_string_pretty(&modules)?)
+				}
+			}
 		}
+		cli::Command::Version(ctx) => {
+			let output = ctx.output;
+			let version = contract::version(ctx)?;
+
+			match output {
+				cli::OutputFormat::Text => println!("{version}"),
+				cli::OutputFormat::Json => {
+					println!("{}", serde_json::to_string_pretty(&version)?)
+				}
+			}
+		}
+		cli::Command::Abi(ctx) => {
+			println!("{}", serde_json::to_string_pretty(&contract::abi(ctx)?)?);
+		}
+	};
+
+	Ok(())
+}
+
+/// Prints the deployed contract's address on its own line, followed by its
+/// metadata CID if it was read.
+fn print_deployment(deployment: &Deployment) {
+	match &deployment.metadata_cid {
+		Some(cid) => println!("{:?} {cid}", deployment.address),
+		None => println!("{:?}", deployment.address),
+	}
+}
+
+/// Reports any progress made by an interrupted command, and writes it to the
+/// state file if there is one, so that a pending deployment can be recovered.
+/// Gets the code to exit with.
+fn report_interruption(
+	state: &DeployState,
+	state_file: Option<&str>,
+	w: &mut impl Write,
+) -> Result<i32, Error> {
+	writeln!(w, "interrupted")?;
+
+	if let Some(cid) = &state.metadata_cid {
+		writeln!(w, "metadata CID: {cid}")?;
+	}
+
+	if let Some(hash) = &state.tx_hash {
+		writeln!(w, "pending transaction: {:?}", hash)?;
+	}
+
+	if let Some(path) = state_file {
+		state.save(path)?;
+	}
+
+	Ok(INTERRUPTED_EXIT_CODE)
+}
+
+/// Stops the IPFS node running in the background, unless it should be left
+/// running, in which case how to reach it is reported.
+fn stop_ipfs(
+	ipfs_handle: Option<&mut Child>,
+	keep_alive: bool,
+	w: &mut impl Write,
+) -> io::Result<()> {
+	let Some(ipfs_handle) = ipfs_handle else {
+		return Ok(());
 	};
 
-	(handle, Ok(()))
+	if keep_alive {
+		writeln!(
+			w,
+			"IPFS node left running at {} with PID {}, logging to {}",
+			cli::DEFAULT_IPFS_GATEWAY,
+			ipfs_handle.id(),
+			cli::ipfs_log_path().display()
+		)
+	} else {
+		ipfs_handle.kill()
+	}
 }
 
 #[actix::main]
@@ -48,12 +248,108 @@ async fn main() -> Result<(), Error> {
 		cli::usage(&mut args);
 	}
 
-	let (ctx, res) = run_cli(args).await;
+	// Will throw an error if not enough args were provided
+	let mut conf = cli::Context::try_from(args)?;
+	let keep_ipfs_alive = conf.cmd.keep_ipfs_alive();
+	let mut ipfs_handle = conf.cmd.take_ipfs_handle();
+	let state_file = conf.cmd.take_state_file();
+
+	let state = Mutex::new(DeployState::default());
+	let res = match future::select(
+		Box::pin(run_cli(conf.cmd, &state)),
+		Box::pin(signal::ctrl_c()),
+	)
+	.await
+	{
+		Either::Left((res, _)) => res,
+		Either::Right(_) => {
+			let code = report_interruption(
+				&state.lock().unwrap(),
+				state_file.as_deref(),
+				&mut io::stderr(),
+			)
+			.expect("failed to write state file");
+
+			stop_ipfs(ipfs_handle.as_mut(), keep_ipfs_alive, &mut io::stderr())
+				.expect("failed to stop IPFS process");
+
+			process::exit(code);
+		}
+	};
 
 	// Stop any IPFS processes running in the background
-	if let Some(mut ipfs_handle) = ctx {
-		ipfs_handle.kill().expect("failed to stop IPFS process");
-	}
+	stop_ipfs(ipfs_handle.as_mut(), keep_ipfs_alive, &mut io::stderr())
+		.expect("failed to stop IPFS process");
 
 	res
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::temp_dir;
+	use std::{fs, process::Command};
+	use web3::types::H256;
+
+	#[test]
+	fn saves_interrupted_deployments() {
+		let dir = temp_dir("interrupted");
+		let path = dir.join("state.json").display().to_string();
+		let state = DeployState {
+			metadata_cid: Some("QmMetadata".to_owned()),
+			tx_hash: Some(H256::repeat_byte(0x22)),
+		};
+
+		let mut reported = Vec::new();
+		let code = report_interruption(&state, Some(&path), &mut reported).unwrap();
+		let saved: serde_json::Value =
+			serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(code, 130);
+		assert_eq!(
+			String::from_utf8(reported).unwrap(),
+			format!(
+				"interrupted\nmetadata CID: QmMetadata\npending transaction: {:?}\n",
+				H256::repeat_byte(0x22)
+			)
+		);
+		assert_eq!(saved["metadata_cid"], "QmMetadata");
+		assert_eq!(saved["tx_hash"], format!("{:?}", H256::repeat_byte(0x22)));
+	}
+
+	#[test]
+	fn reports_interruptions_without_progress() {
+		let mut reported = Vec::new();
+		let code = report_interruption(&DeployState::default(), None, &mut reported).unwrap();
+
+		assert_eq!(code, 130);
+		assert_eq!(reported, b"interrupted\n");
+	}
+
+	#[test]
+	fn stops_ipfs_nodes() {
+		let mut node = Command::new("sleep").arg("30").spawn().unwrap();
+		stop_ipfs(Some(&mut node), false, &mut io::sink()).unwrap();
+		assert!(!node.wait().unwrap().success());
+
+		// Without a node, nothing is reported
+		let mut reported = Vec::new();
+		stop_ipfs(None, true, &mut reported).unwrap();
+		assert!(reported.is_empty());
+	}
+
+	#[test]
+	fn keeps_ipfs_nodes_alive() {
+		let mut node = Command::new("sleep").arg("30").spawn().unwrap();
+		let mut reported = Vec::new();
+		stop_ipfs(Some(&mut node), true, &mut reported).unwrap();
+		let alive = node.try_wait().unwrap().is_none();
+		node.kill().unwrap();
+
+		assert!(alive);
+		assert!(String::from_utf8(reported)
+			.unwrap()
+			.contains(&format!("with PID {}", node.id())));
+	}
+}
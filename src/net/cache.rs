@@ -0,0 +1,181 @@
+use ipfs_api::{Error as IpfsError, IpfsApi, IpfsClient};
+use std::{
+	collections::HashMap,
+	fs::{File, OpenOptions},
+	io::{BufReader, ErrorKind},
+	sync::Mutex,
+};
+use web3::signing::keccak256;
+
+use super::error::Error;
+
+/// A record of files previously uploaded to IPFS, persisted across runs so
+/// that unchanged modules don't have to be re-uploaded.
+pub struct UploadCache {
+	path: String,
+
+	// Maps hex-encoded keccak hashes of uploaded content to their CID's
+	entries: Mutex<HashMap<String, String>>,
+}
+
+impl UploadCache {
+	/// Loads the cache stored at the path, starting with an empty cache if
+	/// the file does not exist yet.
+	pub fn load(path: String) -> Result<Self, Error> {
+		let entries = match OpenOptions::new().read(true).open(&path) {
+			Ok(f) => serde_json::from_reader(BufReader::new(f))?,
+			Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+			Err(e) => return Err(e.into()),
+		};
+
+		Ok(Self {
+			path,
+			entries: Mutex::new(entries),
+		})
+	}
+
+	/// Writes the cache back to the path it was loaded from.
+	pub fn save(&self) -> Result<(), Error> {
+		let f = File::create(&self.path)?;
+		serde_json::to_writer_pretty(f, &*self.entries.lock().unwrap())?;
+
+		Ok(())
+	}
+
+	/// Gets the CID of the content if it was uploaded previously, and the
+	/// IPFS node still has it pinned. Entries that the node reports as no
+	/// longer pinned are evicted.
+	pub async fn get(&self, ipfs: &IpfsClient, content: &[u8]) -> Option<String> {
+		let hash = content_hash(content);
+		let cid = self.entries.lock().unwrap().get(&hash).cloned()?;
+
+		match ipfs.pin_ls(Some(&cid), None).await {
+			Ok(_) => {
+				log::debug!("reusing cached upload {cid}");
+
+				Some(cid)
+			}
+			Err(IpfsError::Api(e)) if e.message.contains("not pinned") => {
+				log::debug!("evicting stale upload cache entry {cid}");
+				self.entries.lock().unwrap().remove(&hash);
+
+				None
+			}
+			Err(e) => {
+				// The entry may well still be good once the node is back
+				log::debug!("couldn't check cached upload {cid}: {e}");
+
+				None
+			}
+		}
+	}
+
+	/// Records that the content was uploaded with the given CID.
+	pub fn insert(&self, content: &[u8], cid: String) {
+		self.entries
+			.lock()
+			.unwrap()
+			.insert(content_hash(content), cid);
+	}
+}
+
+/// Gets the key under which the content is stored in the cache.
+fn content_hash(content: &[u8]) -> String {
+	hex::encode(keccak256(content))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{fake_node, missing_node, temp_dir};
+	use std::fs;
+
+	const PINNED: &str = r#"{"Keys":{}}"#;
+	const NOT_PINNED: &str =
+		r#"{"Message":"path 'QmModule' is not pinned","Code":0,"Type":"error"}"#;
+
+	fn cache_at(name: &str) -> UploadCache {
+		UploadCache::load(temp_dir(name).join("cache.json").display().to_string()).unwrap()
+	}
+
+	#[actix_rt::test]
+	async fn misses_unknown_content() {
+		let cache = cache_at("cache-miss");
+
+		assert_eq!(cache.get(&fake_node(200, PINNED), b"module").await, None);
+	}
+
+	#[actix_rt::test]
+	async fn hits_pinned_content() {
+		let cache = cache_at("cache-hit");
+		cache.insert(b"module", "QmModule".to_owned());
+
+		let node = fake_node(200, PINNED);
+		assert_eq!(
+			cache.get(&node, b"module").await.as_deref(),
+			Some("QmModule")
+		);
+		assert_eq!(cache.get(&node, b"other").await, None);
+	}
+
+	#[actix_rt::test]
+	async fn evicts_unpinned_content() {
+		let cache = cache_at("cache-evict");
+		cache.insert(b"module", "QmModule".to_owned());
+
+		assert_eq!(
+			cache.get(&fake_node(500, NOT_PINNED), b"module").await,
+			None
+		);
+
+		// The entry is gone even once the content is pinned again
+		assert_eq!(cache.get(&fake_node(200, PINNED), b"module").await, None);
+	}
+
+	#[actix_rt::test]
+	async fn keeps_entries_while_node_is_down() {
+		let cache = cache_at("cache-down");
+		cache.insert(b"module", "QmModule".to_owned());
+
+		assert_eq!(cache.get(&missing_node(), b"module").await, None);
+		assert_eq!(
+			cache
+				.get(
+					&fake_node(500, r#"{"Message":"busy","Code":0,"Type":"error"}"#),
+					b"module"
+				)
+				.await,
+			None
+		);
+
+		assert_eq!(
+			cache
+				.get(&fake_node(200, PINNED), b"module")
+				.await
+				.as_deref(),
+			Some("QmModule")
+		);
+	}
+
+	#[test]
+	fn persists_entries() {
+		let dir = temp_dir("cache-persist");
+		let path = dir.join("cache.json").display().to_string();
+		let cache = UploadCache::load(path.clone()).unwrap();
+		cache.insert(b"module", "QmModule".to_owned());
+		cache.save().unwrap();
+
+		let loaded = UploadCache::load(path).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(
+			loaded
+				.entries
+				.lock()
+				.unwrap()
+				.get(&content_hash(b"module"))
+				.map(String::as_str),
+			Some("QmModule")
+		);
+	}
+}
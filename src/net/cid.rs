@@ -0,0 +1,363 @@
+use multibase::Base;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::{iter::Peekable, slice::Chunks};
+
+use super::error::Error;
+
+/// The version and multicodec of the CID's of DAG nodes, which IPFS stores as
+/// dag-cbor.
+const DAG_CBOR_CID_PREFIX: [u8; 2] = [0x01, 0x71];
+
+/// The multihash code and digest length of sha2-256, which IPFS hashes DAG
+/// nodes with.
+const SHA2_256_PREFIX: [u8; 2] = [0x12, 0x20];
+
+/// The CBOR tag marking a CID.
+const CID_TAG: u64 = 42;
+
+/// The size of the chunks that `ipfs add` splits files into, and the most
+/// links that a node of a file's DAG has.
+const CHUNK_SIZE: usize = 262_144;
+const MAX_LINKS: usize = 174;
+
+/// The UnixFS data type of a file node.
+const UNIXFS_FILE: u64 = 2;
+
+/// Computes the CID that IPFS gives the dag-json node when it is put, without
+/// storing it.
+pub fn dag_node_cid(node: &Value) -> Result<String, Error> {
+	let mut encoded = Vec::new();
+	encode(node, &mut encoded)?;
+
+	let mut cid = DAG_CBOR_CID_PREFIX.to_vec();
+	cid.extend_from_slice(&multihash(&encoded));
+
+	Ok(multibase::encode(Base::Base32Lower, cid))
+}
+
+/// Computes the CID that `ipfs add` gives the file's content with its default
+/// options, without storing it.
+pub fn file_cid(content: &[u8]) -> String {
+	let mut chunks = content.chunks(CHUNK_SIZE).peekable();
+
+	// Files are balanced trees of chunks, deepened whenever the root fills up
+	let mut root = leaf(chunks.next().unwrap_or_default());
+	let mut depth = 1;
+
+	while chunks.peek().is_some() {
+		root = fill(vec![root], &mut chunks, depth);
+		depth += 1;
+	}
+
+	Base::Base58Btc.encode(multihash(&root.encoded))
+}
+
+/// An encoded dag-pb node of a file's DAG.
+struct FileNode {
+	encoded: Vec<u8>,
+
+	// The bytes of the file under the node, and the bytes of the node and
+	// all of the nodes under it
+	file_size: u64,
+	dag_size: u64,
+}
+
+/// Makes a node holding a chunk of a file.
+fn leaf(chunk: &[u8]) -> FileNode {
+	let mut unixfs = Vec::new();
+	write_varint_field(1, UNIXFS_FILE, &mut unixfs);
+	if !chunk.is_empty() {
+		write_bytes_field(2, chunk, &mut unixfs);
+	}
+	write_varint_field(3, chunk.len() as u64, &mut unixfs);
+
+	let mut encoded = Vec::new();
+	write_bytes_field(1, &unixfs, &mut encoded);
+
+	FileNode {
+		dag_size: encoded.len() as u64,
+		file_size: chunk.len() as u64,
+		encoded,
+	}
+}
+
+/// Adds nodes under the given children, each holding a tree of the depth
+/// below, until the node is full or the chunks run out. Gets the parent of
+/// the children.
+fn fill(
+	mut children: Vec<FileNode>,
+	chunks: &mut Peekable<Chunks<'_, u8>>,
+	depth: usize,
+) -> FileNode {
+	while children.len() < MAX_LINKS {
+		let Some(chunk) = chunks.peek().copied() else {
+			break;
+		};
+
+		children.push(if depth == 1 {
+			chunks.next();
+			leaf(chunk)
+		} else {
+			fill(Vec::new(), chunks, depth - 1)
+		});
+	}
+
+	branch(&children)
+}
+
+/// Makes a node linking to the children, in order.
+fn branch(children: &[FileNode]) -> FileNode {
+	let file_size = children.iter().map(|child| child.file_size).sum();
+
+	let mut unixfs = Vec::new();
+	write_varint_field(1, UNIXFS_FILE, &mut unixfs);
+	write_varint_field(3, file_size, &mut unixfs);
+	for child in children {
+		write_varint_field(4, child.file_size, &mut unixfs);
+	}
+
+	// Links come before the data in dag-pb, and are always named, if only
+	// with an empty name
+	let mut encoded = Vec::new();
+	for child in children {
+		let mut link = Vec::new();
+		write_bytes_field(1, &multihash(&child.encoded), &mut link);
+		write_bytes_field(2, &[], &mut link);
+		write_varint_field(3, child.dag_size, &mut link);
+
+		write_bytes_field(2, &link, &mut encoded);
+	}
+	write_bytes_field(1, &unixfs, &mut encoded);
+
+	FileNode {
+		dag_size: encoded.len() as u64 + children.iter().map(|child| child.dag_size).sum::<u64>(),
+		file_size,
+		encoded,
+	}
+}
+
+/// Gets the sha2-256 multihash of the bytes.
+fn multihash(bytes: &[u8]) -> Vec<u8> {
+	let mut hash = SHA2_256_PREFIX.to_vec();
+	hash.extend_from_slice(&Sha256::digest(bytes));
+
+	hash
+}
+
+/// Writes a protobuf varint field.
+fn write_varint_field(field: u64, value: u64, out: &mut Vec<u8>) {
+	write_varint(field << 3, out);
+	write_varint(value, out);
+}
+
+/// Writes a length-delimited protobuf field.
+fn write_bytes_field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+	write_varint((field << 3) | 2, out);
+	write_varint(bytes.len() as u64, out);
+	out.extend_from_slice(bytes);
+}
+
+/// Writes a protobuf varint, seven bits at a time from the lowest.
+fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+	while n >= 0x80 {
+		out.push(n as u8 | 0x80);
+		n >>= 7;
+	}
+
+	out.push(n as u8);
+}
+
+/// Encodes the dag-json value as dag-cbor, in which links are tagged CID's.
+fn encode(value: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+	match value {
+		Value::Null => out.push(0xf6),
+		Value::Bool(false) => out.push(0xf4),
+		Value::Bool(true) => out.push(0xf5),
+		Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+			(Some(n), _, _) => write_head(0, n, out),
+			(None, Some(n), _) => write_head(1, n.unsigned_abs() - 1, out),
+
+			// Floats are always encoded at full width
+			(None, None, Some(n)) => {
+				out.push(0xfb);
+				out.extend_from_slice(&n.to_be_bytes());
+			}
+			(None, None, None) => return Err(Error::InvalidInput),
+		},
+		Value::String(s) => write_str(s, out),
+		Value::Array(items) => {
+			write_head(4, items.len() as u64, out);
+
+			for item in items {
+				encode(item, out)?;
+			}
+		}
+		Value::Object(map) => match link(map) {
+			Some(cid) => {
+				// Binary CID's are prefixed with a zero byte in dag-cbor
+				let cid = cid_bytes(cid)?;
+
+				write_head(6, CID_TAG, out);
+				write_head(2, cid.len() as u64 + 1, out);
+				out.push(0);
+				out.extend_from_slice(&cid);
+			}
+			None => {
+				// Keys are sorted by length, then bytewise
+				let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+				entries.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then(a.cmp(b)));
+
+				write_head(5, entries.len() as u64, out);
+
+				for (key, value) in entries {
+					write_str(key, out);
+					encode(value, out)?;
+				}
+			}
+		},
+	}
+
+	Ok(())
+}
+
+/// Gets the CID that the map links to, if it is a dag-json link.
+fn link(map: &Map<String, Value>) -> Option<&str> {
+	match map.get("/") {
+		Some(Value::String(cid)) if map.len() == 1 => Some(cid),
+		_ => None,
+	}
+}
+
+/// Gets a link to the content with the CID through the HTTP gateway, for
+/// showing to people.
+pub fn gateway_link(gateway: &str, cid: &str) -> String {
+	format!("{}/ipfs/{cid}", gateway.trim_end_matches('/'))
+}
+
+/// Decodes a string-encoded CID into its binary form.
+fn cid_bytes(cid: &str) -> Result<Vec<u8>, Error> {
+	// CIDv0's are bare base58-encoded multihashes
+	let bytes = if cid.starts_with("Qm") {
+		Base::Base58Btc.decode(cid).ok()
+	} else {
+		multibase::decode(cid).ok().map(|(_, bytes)| bytes)
+	};
+
+	bytes.ok_or(Error::InvalidInput)
+}
+
+/// Writes a CBOR text string.
+fn write_str(s: &str, out: &mut Vec<u8>) {
+	write_head(3, s.len() as u64, out);
+	out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes the head of a CBOR data item, made up of its major type and an
+/// argument in the shortest form that fits it.
+fn write_head(major: u8, arg: u64, out: &mut Vec<u8>) {
+	let major = major << 5;
+
+	match arg {
+		0..=23 => out.push(major | arg as u8),
+		24..=0xff => out.extend_from_slice(&[major | 24, arg as u8]),
+		0x100..=0xffff => {
+			out.push(major | 25);
+			out.extend_from_slice(&(arg as u16).to_be_bytes());
+		}
+		0x1_0000..=0xffff_ffff => {
+			out.push(major | 26);
+			out.extend_from_slice(&(arg as u32).to_be_bytes());
+		}
+		_ => {
+			out.push(major | 27);
+			out.extend_from_slice(&arg.to_be_bytes());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn computes_small_file_cids() {
+		assert_eq!(
+			file_cid(b""),
+			"QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+		);
+		assert_eq!(
+			file_cid(b"hello world\n"),
+			"QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+		);
+	}
+
+	#[test]
+	fn encodes_empty_files() {
+		assert_eq!(leaf(b"").encoded, [0x0a, 0x04, 0x08, 0x02, 0x18, 0x00]);
+	}
+
+	#[test]
+	fn splits_large_files() {
+		let content = vec![7; CHUNK_SIZE * 2 + 1];
+		let mut chunks = content.chunks(CHUNK_SIZE).peekable();
+		let first = leaf(chunks.next().unwrap());
+		let root = fill(vec![first], &mut chunks, 1);
+
+		assert_eq!(root.file_size, content.len() as u64);
+		assert_eq!(
+			file_cid(&content),
+			Base::Base58Btc.encode(multihash(&root.encoded))
+		);
+
+		// The root links to each chunk, counting their sizes
+		let leaves: u64 = content
+			.chunks(CHUNK_SIZE)
+			.map(|chunk| leaf(chunk).dag_size)
+			.sum();
+		assert_eq!(root.dag_size, root.encoded.len() as u64 + leaves);
+	}
+
+	#[test]
+	fn deepens_full_trees() {
+		// One chunk more than a full root holds needs another level
+		let content = vec![0; CHUNK_SIZE * MAX_LINKS + 1];
+		let mut chunks = content.chunks(CHUNK_SIZE).peekable();
+		let first = leaf(chunks.next().unwrap());
+		let full = fill(vec![first], &mut chunks, 1);
+		let root = fill(vec![full], &mut chunks, 2);
+
+		assert!(chunks.next().is_none());
+		assert_eq!(root.file_size, content.len() as u64);
+		assert_eq!(
+			file_cid(&content),
+			Base::Base58Btc.encode(multihash(&root.encoded))
+		);
+	}
+
+	#[test]
+	fn computes_dag_node_cids() {
+		// An empty dag-cbor map
+		assert_eq!(
+			dag_node_cid(&serde_json::json!({})).unwrap(),
+			"bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua"
+		);
+	}
+
+	#[test]
+	fn encodes_varints() {
+		let mut out = Vec::new();
+		write_varint(300, &mut out);
+		assert_eq!(out, [0xac, 0x02]);
+	}
+
+	#[test]
+	fn links_through_gateways() {
+		for gateway in ["https://dweb.link", "https://dweb.link/"] {
+			assert_eq!(
+				gateway_link(gateway, "QmMetadata"),
+				"https://dweb.link/ipfs/QmMetadata"
+			);
+		}
+	}
+}
@@ -1,188 +1,3197 @@
-use futures::stream::{self, StreamExt};
+use actix_rt::{task, time};
+use futures::{
+	channel::mpsc::{self, UnboundedSender},
+	future,
+	stream::{self, PollNext, StreamExt, TryStreamExt},
+};
+use ipfs_api::response::Cid;
 use secp256k1::SecretKey;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{fs::OpenOptions, io::BufReader, str::FromStr};
+use std::{
+	fmt,
+	fs::{self, File, OpenOptions},
+	future::Future,
+	io::{self, BufReader, ErrorKind, IsTerminal},
+	path::{Path, PathBuf},
+	slice,
+	str::FromStr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
 use web3::{
-	api::Web3,
-	contract::{Contract, Options},
-	error::Error as Web3Error,
+	api::{Eth, Web3},
+	confirm,
+	contract::{deploy::Error as DeployError, tokens::Tokenize, Contract, Options},
+	ethabi::{self, ParamType, Token},
 	signing::{Key, SecretKeyRef},
 	transports::Http,
-	types::{Address, BlockId, BlockNumber, Bytes, Transaction, TransactionReceipt, H256, U256},
+	types::{
+		AccessList, Address, BlockId, BlockNumber, Bytes, CallRequest, Transaction,
+		TransactionParameters, TransactionReceipt, H256, U256, U64,
+	},
+	Transport,
 };
 
 use super::{
-	super::cli::{ListContext, NewContext},
+	super::cli::{
+		AbiContext, Artifact, ArtifactFormat, Confirmation, ListContext, Metadata, MetadataContext,
+		Module, ModulesContext, ModulesTarget, NewContext, OutputFormat, VersionContext, L2,
+	},
+	cache::UploadCache,
+	cid,
+	ens::AddressResolver,
 	error::Error,
-	payload::deploy_metadata,
+	export,
+	payload::{self, deploy_metadata, InstalledModule, MetadataDetails},
 };
 
-/// Details of the Beacon DAO
-const DEFAULT_NAME: &str = "Vision DAO";
-const DEFAULT_DESCRIPTION: &str =
-	"The Vision DAO is a DAO that governs the Beacon DAO layer of the Vision ecosystem.";
-const DEFAULT_SYMBOL: &str = "VIS";
+/// The maximum amount of gas the deployment may use.
+const GAS_LIMIT: u64 = 4_000_000;
+
+/// Arbitrum charges for the L1 calldata of a transaction in L2 gas, so
+/// deployments there need a higher limit.
+const ARBITRUM_GAS_LIMIT_MULTIPLIER: u64 = 4;
+
+/// OP-stack chains charge an L1 data fee on top of the gas used, which is
+/// budgeted for as a percentage of the gas cost.
+const OPTIMISM_L1_FEE_PERCENT: u64 = 50;
+
+/// The type of transactions that carry an EIP-2930 access list.
+const ACCESS_LIST_TX_TYPE: u64 = 1;
+
+/// How often the node is polled while waiting for the deployment to confirm.
+const POLL_INTERVAL: Duration = Duration::from_secs(7);
+
+/// The number of blocks that must be built on the deployment before it is
+/// considered successful, unless overridden.
+const CONFIRMATIONS: usize = 2;
+
+/// The number of characters in the progress bar shown while scanning.
+const PROGRESS_WIDTH: u64 = 40;
+
+/// The getter of the Idea contract that returns the CID of its metadata.
+const METADATA_GETTER: &str = "ipfsAddr";
+
+/// How many blocks are scanned between writes of the checkpoint.
+const CHECKPOINT_INTERVAL: u64 = 100;
+
+/// Progress of an in-flight deployment, recorded so that a deployment that
+/// gets interrupted can be recovered.
+#[derive(Serialize, Default, Debug)]
+pub struct DeployState {
+	pub(crate) metadata_cid: Option<String>,
+	pub(crate) tx_hash: Option<H256>,
+}
+
+impl DeployState {
+	/// Writes the state as JSON to the file at the path.
+	pub fn save(&self, path: &str) -> Result<(), Error> {
+		serde_json::to_writer_pretty(File::create(path)?, self)?;
+
+		Ok(())
+	}
+}
 
-// 1_000_000 * 10^18
-const DEFAULT_SUPPLY: U256 = U256([2003764205206896640, 54210, 0, 0]);
+/// A milestone reached by a deployment, reported to embedders that want to
+/// show progress.
+#[derive(Debug)]
+pub enum DeployEvent {
+	MetadataUploaded { cid: String },
+	GasLimitSet { gas_limit: u64 },
+	Broadcast { tx_hash: H256 },
+	Confirmed { address: Address },
+}
 
 /// A JSON object that can be deployed as a contract by having a specified bytecode.
-#[derive(Deserialize)]
-struct DeployableContract {
+pub(crate) struct DeployableContract {
 	bytecode: String,
 	abi: Value,
 }
 
-/// Gets the bytecode of the Idea.sol contract in the specified contracts dir.
+/// Gets the bytecode of the Idea.sol contract in the specified artifact.
 /// Returns the raw source of the contract, and the bytecode.
-fn with_contract(contracts_dir: String) -> Result<(Vec<u8>, DeployableContract), Error> {
-	let f = OpenOptions::new()
-		.read(true)
-		.open(format!("{contracts_dir}/contracts/Idea.sol/Idea.json"))?;
-	let src_reader = BufReader::new(f);
+pub(crate) fn with_contract(artifact: &Artifact) -> Result<(Vec<u8>, DeployableContract), Error> {
+	let (path, format, src) = read_artifact(artifact)?;
+
+	// Combined and Hardhat artifacts are laid out the same, so only Foundry's
+	// can be told apart by their fields
+	let format = format.unwrap_or_else(|| {
+		let format = if src.pointer("/bytecode/object").is_some() {
+			ArtifactFormat::Foundry
+		} else {
+			ArtifactFormat::Combined
+		};
 
-	let parsed: DeployableContract = serde_json::from_reader(src_reader)?;
+		log::debug!("detected {:?} artifact at {}", format, path.display());
+
+		format
+	});
+
+	// Some toolchains emit the bytecode separately from the ABI
+	let bytecode = artifact
+		.bytecode_file
+		.as_deref()
+		.map(read_bytecode)
+		.transpose()?;
+	let parsed = parse_artifact(src, format, bytecode)?;
 	let src = serde_json::to_vec(&parsed.abi)?;
 
 	// Extract the bytecode from the compiled contract
 	Ok((src, parsed))
 }
 
+/// Reads the ABI of the Idea contract from its artifact, as it appears there.
+pub fn abi(AbiContext { artifact }: AbiContext) -> Result<Value, Error> {
+	Ok(with_contract(&artifact)?.1.abi)
+}
+
+/// Reads the artifact, along with its path and the format it was built in if
+/// it is known.
+fn read_artifact(artifact: &Artifact) -> Result<(PathBuf, Option<ArtifactFormat>, Value), Error> {
+	let (path, format) = locate_artifact(artifact)?;
+	let f = OpenOptions::new()
+		.read(true)
+		.open(&path)
+		.map_err(|e| Error::MissingArtifact(path.display().to_string(), e))?;
+	let src = serde_json::from_reader(BufReader::new(f))?;
+
+	Ok((path, format, src))
+}
+
+/// The version of daowiz, and how the Idea contract it deploys was built.
+#[derive(Serialize)]
+pub struct Version {
+	daowiz: &'static str,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	artifact: Option<BuildInfo>,
+}
+
+/// The details of how the Idea contract was compiled that its artifact
+/// records. Not every toolchain records them in the artifact.
+#[derive(Serialize)]
+pub struct BuildInfo {
+	path: String,
+	compiler: Option<String>,
+	optimizer: Option<Optimizer>,
+}
+
+/// The solc optimizer settings the contract was compiled with.
+#[derive(Serialize, Deserialize)]
+pub struct Optimizer {
+	enabled: bool,
+	runs: Option<u64>,
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(w, "daowiz {}", self.daowiz)?;
+
+		if let Some(BuildInfo {
+			path,
+			compiler,
+			optimizer,
+		}) = &self.artifact
+		{
+			write!(w, "\nartifact: {path}")?;
+			write!(
+				w,
+				"\ncompiler: {}",
+				compiler.as_deref().unwrap_or("unknown")
+			)?;
+
+			match optimizer {
+				Some(Optimizer {
+					enabled: true,
+					runs: Some(runs),
+				}) => write!(w, "\noptimizer: enabled, {runs} runs")?,
+				Some(Optimizer { enabled: true, .. }) => write!(w, "\noptimizer: enabled")?,
+				Some(Optimizer { enabled: false, .. }) => write!(w, "\noptimizer: disabled")?,
+				None => write!(w, "\noptimizer: unknown")?,
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Gets the version of daowiz, and the build details recorded in the artifact
+/// if one was specified.
+pub fn version(VersionContext { artifact, .. }: VersionContext) -> Result<Version, Error> {
+	let artifact = match artifact {
+		Some(artifact) => {
+			let (path, _, src) = read_artifact(&artifact)?;
+
+			// Foundry records solc's metadata as an object, and combined
+			// artifacts as a JSON string, sometimes with the compiler on its
+			// own
+			let metadata = match src.get("metadata") {
+				Some(Value::String(raw)) => serde_json::from_str(raw).ok(),
+				Some(metadata @ Value::Object(_)) => Some(metadata.clone()),
+				_ => None,
+			};
+			let compiler = metadata
+				.as_ref()
+				.and_then(|metadata| metadata.pointer("/compiler/version"))
+				.or_else(|| src.pointer("/compiler/version"))
+				.and_then(Value::as_str)
+				.map(str::to_owned);
+			let optimizer = metadata
+				.as_ref()
+				.and_then(|metadata| metadata.pointer("/settings/optimizer"))
+				.and_then(|optimizer| serde_json::from_value(optimizer.clone()).ok());
+
+			Some(BuildInfo {
+				path: path.display().to_string(),
+				compiler,
+				optimizer,
+			})
+		}
+		None => None,
+	};
+
+	Ok(Version {
+		daowiz: env!("CARGO_PKG_VERSION"),
+		artifact,
+	})
+}
+
+/// Gets the path of the artifact, and the format it was built in if it is
+/// known from the flags or the contracts dir's layout. Fails if the layout
+/// matches multiple formats.
+pub(crate) fn locate_artifact(
+	artifact: &Artifact,
+) -> Result<(PathBuf, Option<ArtifactFormat>), Error> {
+	let dir = Path::new(&artifact.contracts_dir);
+
+	match (&artifact.path, artifact.format) {
+		(Some(path), format) => Ok((dir.join(path), format)),
+		(None, Some(format)) => Ok((dir.join(format.default_path()), Some(format))),
+		(None, None) => {
+			// Probe each toolchain's conventional path
+			let mut found = ArtifactFormat::ALL
+				.into_iter()
+				.map(|format| (dir.join(format.default_path()), Some(format)))
+				.filter(|(path, _)| path.is_file())
+				.collect::<Vec<_>>();
+
+			match found.len() {
+				// Report the conventional path as missing
+				0 => Ok((
+					dir.join(ArtifactFormat::Combined.default_path()),
+					Some(ArtifactFormat::Combined),
+				)),
+				1 => {
+					let (path, format) = found.remove(0);
+					log::debug!("detected {:?} artifact at {}", format, path.display());
+
+					Ok((path, format))
+				}
+				_ => Err(Error::AmbiguousArtifact(
+					found
+						.into_iter()
+						.map(|(path, _)| path.display().to_string())
+						.collect(),
+				)),
+			}
+		}
+	}
+}
+
+/// Reads the EIP-2930 access list in the JSON file at the path, which is an
+/// array of entries with an `address` and its `storageKeys`.
+fn read_access_list(path: &str) -> Result<AccessList, Error> {
+	let f = OpenOptions::new().read(true).open(path)?;
+
+	serde_json::from_reader(BufReader::new(f))
+		.map_err(|e| Error::InvalidAccessList(path.to_owned(), e))
+}
+
+/// Reads the raw hex bytecode in the .bin file at the path, prefixed like
+/// the bytecode in JSON artifacts.
+fn read_bytecode(path: &str) -> Result<String, Error> {
+	let src =
+		fs::read_to_string(path).map_err(|e| Error::MissingBytecodeFile(path.to_owned(), e))?;
+	let code = src.trim();
+	let code = code.strip_prefix("0x").unwrap_or(code);
+
+	if hex::decode(code).is_err() {
+		return Err(Error::InvalidBytecode(path.to_owned()));
+	}
+
+	Ok(format!("0x{code}"))
+}
+
+/// Checks that the compiled contract has the fields needed to deploy it,
+/// naming the first field that is missing or malformed. The artifact's
+/// bytecode isn't needed if it was loaded from elsewhere.
+fn parse_artifact(
+	mut artifact: Value,
+	format: ArtifactFormat,
+	bytecode: Option<String>,
+) -> Result<DeployableContract, Error> {
+	// Where the format keeps the bytecode, as a JSON pointer and as named in
+	// errors
+	let (pointer, field) = match format {
+		// Hardhat's deployedBytecode is the runtime code, which can't be
+		// deployed
+		ArtifactFormat::Combined | ArtifactFormat::Hardhat => ("/bytecode", "bytecode"),
+		ArtifactFormat::Foundry => ("/bytecode/object", "bytecode.object"),
+	};
+
+	let bytecode = match bytecode {
+		Some(bytecode) => bytecode,
+		None => match artifact.pointer_mut(pointer).map(Value::take) {
+			// The bytecode is always 0x-prefixed for encoding, like a .bin
+			// file's
+			Some(Value::String(bytecode)) => {
+				let code = bytecode.strip_prefix("0x").unwrap_or(&bytecode);
+
+				if hex::decode(code).is_err() {
+					return Err(Error::InvalidArtifact(field, "a hex string"));
+				}
+
+				format!("0x{code}")
+			}
+			Some(_) => return Err(Error::InvalidArtifact(field, "a hex string")),
+			None => return Err(Error::InvalidArtifact(field, "present")),
+		},
+	};
+	let abi = match artifact.get_mut("abi").map(Value::take) {
+		Some(abi @ Value::Array(_)) => abi,
+		Some(_) => return Err(Error::InvalidArtifact("abi", "an array")),
+		None => return Err(Error::InvalidArtifact("abi", "present")),
+	};
+
+	Ok(DeployableContract { bytecode, abi })
+}
+
+/// Uploads the metadata of the Beacon DAO using the details specified by the
+/// context, returning the CID of the metadata.
+pub async fn publish(
+	MetadataContext {
+		title,
+		description,
+		compress_description,
+		icon,
+		schema_version,
+		modules,
+		module_options,
+		ipfs,
+		upload_cache,
+		hash_only,
+		print_metadata,
+		replication_check,
+		require_replication,
+		check_ipfs_storage,
+		..
+	}: MetadataContext,
+) -> Result<Cid, Error> {
+	// Deploy the metadata required for the contract, including specified
+	// payloads. Nothing is uploaded to record in the cache if only the CID
+	// is computed
+	if check_ipfs_storage && !hash_only {
+		let incoming = modules
+			.iter()
+			.map(|m| Ok::<_, io::Error>(m.loader.metadata()?.len() + m.module.metadata()?.len()))
+			.sum::<Result<u64, _>>()?;
+
+		payload::check_storage(&ipfs, incoming).await;
+	}
+
+	let cache = upload_cache
+		.filter(|_| !hash_only)
+		.map(UploadCache::load)
+		.transpose()?;
+	let meta = deploy_metadata(
+		&ipfs,
+		cache.as_ref(),
+		MetadataDetails {
+			title: &title,
+			description: &description,
+			compress_description,
+			icon: icon.as_deref(),
+			schema_version,
+		},
+		modules,
+		&module_options,
+		hash_only,
+		print_metadata,
+	)
+	.await?;
+
+	if let Some(cache) = cache {
+		cache.save()?;
+	}
+
+	// Content held only by the uploading node is lost with it
+	if let Some(replica) = replication_check.filter(|_| !hash_only) {
+		let unreachable = payload::check_replication(&ipfs, &replica, &meta.cid_string).await?;
+
+		if !unreachable.is_empty() {
+			let e = Error::Unreplicated(unreachable);
+
+			if require_replication {
+				return Err(e);
+			}
+
+			eprintln!("warning: {e}");
+		}
+	}
+
+	Ok(meta)
+}
+
+/// Chain state needed to deploy the Beacon DAO.
+struct Preflight {
+	nonce: U256,
+	gas_price: U256,
+	gas_limit: u64,
+
+	// The most the deployment may cost, including any L1 fees
+	cost: U256,
+}
+
+/// Gets the gas limit of the deployment, and the most it may cost at the gas
+/// price, adjusted for how the L2 charges fees.
+fn fees(gas_price: U256, l2: Option<L2>) -> (u64, U256) {
+	match l2 {
+		Some(L2::Arbitrum) => {
+			let gas_limit = GAS_LIMIT * ARBITRUM_GAS_LIMIT_MULTIPLIER;
+
+			(gas_limit, gas_price * gas_limit)
+		}
+		Some(L2::Optimism) => {
+			let cost = gas_price * GAS_LIMIT;
+
+			(GAS_LIMIT, cost + cost * OPTIMISM_L1_FEE_PERCENT / 100)
+		}
+		None => (GAS_LIMIT, gas_price * GAS_LIMIT),
+	}
+}
+
+/// Checks that the node is on the expected chain, and that the deployer can
+/// afford the deployment, returning the chain state needed for deploying.
+async fn preflight<T: Transport>(
+	web3: &Web3<T>,
+	from: Address,
+	chain_id: u64,
+	l2: Option<L2>,
+	at_block: Option<BlockNumber>,
+) -> Result<Preflight, Error> {
+	let eth = web3.eth();
+	let (actual_chain_id, nonce, gas_price, balance) = futures::try_join!(
+		eth.chain_id(),
+		eth.transaction_count(from, None),
+		eth.gas_price(),
+		eth.balance(from, at_block)
+	)?;
+
+	if actual_chain_id != chain_id.into() {
+		return Err(Error::ChainIdMismatch(chain_id, actual_chain_id));
+	}
+
+	let (gas_limit, cost) = fees(gas_price, l2);
+	if balance < cost {
+		return Err(Error::InsufficientFunds(balance, cost));
+	}
+
+	log::debug!("passed preflight checks for {:?}", from);
+
+	Ok(Preflight {
+		nonce,
+		gas_price,
+		gas_limit,
+		cost,
+	})
+}
+
+/// What deploying with the details will do, to be approved by the deployer.
+#[derive(Serialize)]
+pub struct Plan {
+	eth_uri: String,
+	eth_chain_id: u64,
+	name: String,
+	symbol: String,
+	supply: U256,
+	metadata: PlannedMetadata,
+
+	// The gas limit of the deployment, and the most it may cost in wei
+	gas_limit: u64,
+	gas_price: U256,
+	cost: U256,
+}
+
+/// Where the DAO's metadata comes from in a deployment plan.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum PlannedMetadata {
+	// The modules uploaded, and the IPFS node that they're pinned to
+	Upload {
+		modules: Vec<PlannedModule>,
+		ipfs_uri: String,
+	},
+
+	// A gateway link to metadata that already exists
+	Existing {
+		link: String,
+	},
+}
+
+/// A module uploaded by a deployment plan, and the sizes of its files in
+/// bytes.
+#[derive(Serialize)]
+struct PlannedModule {
+	name: String,
+	loader_bytes: u64,
+	module_bytes: u64,
+}
+
+impl fmt::Display for Plan {
+	fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(w, "deployment plan:")?;
+		writeln!(w, "  chain: {} via {}", self.eth_chain_id, self.eth_uri)?;
+		writeln!(
+			w,
+			"  DAO: {} ({}), with a supply of {}",
+			self.name, self.symbol, self.supply
+		)?;
+
+		match &self.metadata {
+			PlannedMetadata::Upload { modules, ipfs_uri } => {
+				writeln!(w, "  modules:")?;
+
+				for PlannedModule {
+					name,
+					loader_bytes,
+					module_bytes,
+				} in modules
+				{
+					writeln!(
+						w,
+						"    {name}: {loader_bytes} byte loader, {module_bytes} byte module"
+					)?;
+				}
+
+				writeln!(w, "  metadata: pinned to {ipfs_uri}")?;
+			}
+			PlannedMetadata::Existing { link } => writeln!(w, "  metadata: existing {link}")?,
+		}
+
+		writeln!(
+			w,
+			"  gas: limit of {} at {} wei, costing up to {} wei",
+			self.gas_limit, self.gas_price, self.cost
+		)
+	}
+}
+
+/// Describes what deploying with the details will do, in a form to be
+/// approved by the deployer.
+fn plan_deployment(
+	eth_uri: &str,
+	eth_chain_id: u64,
+	(name, symbol, supply): (&str, &str, U256),
+	metadata: &Metadata,
+	Preflight {
+		gas_price,
+		gas_limit,
+		cost,
+		..
+	}: &Preflight,
+	gateway: &str,
+) -> Result<Plan, Error> {
+	Ok(Plan {
+		eth_uri: eth_uri.to_owned(),
+		eth_chain_id,
+		name: name.to_owned(),
+		symbol: symbol.to_owned(),
+		supply,
+		metadata: match metadata {
+			Metadata::Upload(ctx) => PlannedMetadata::Upload {
+				modules: ctx
+					.modules
+					.iter()
+					.map(
+						|Module {
+						     name,
+						     loader,
+						     module,
+						 }| {
+							Ok(PlannedModule {
+								name: name.clone(),
+								loader_bytes: loader.metadata()?.len(),
+								module_bytes: module.metadata()?.len(),
+							})
+						},
+					)
+					.collect::<Result<_, Error>>()?,
+				ipfs_uri: ctx.ipfs_uri.clone(),
+			},
+			Metadata::Existing(cid) => PlannedMetadata::Existing {
+				link: cid::gateway_link(gateway, cid),
+			},
+		},
+		gas_limit: *gas_limit,
+		gas_price: *gas_price,
+		cost: *cost,
+	})
+}
+
+/// Time spent waiting on the deployer to approve the plan, which doesn't
+/// count against the deployment's timeout.
+#[derive(Default)]
+struct PromptTime {
+	since: Option<Instant>,
+	total: Duration,
+}
+
+impl PromptTime {
+	/// Pushes the deadline back by the time spent prompting so far.
+	fn extend(&self, deadline: Instant) -> Instant {
+		deadline + self.total + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+	}
+}
+
+/// Asks the deployer whether to go ahead, treating anything but yes as no.
+/// The answer is read on a blocking thread, so that Ctrl-C is still handled
+/// while waiting for it.
+async fn confirm(prompted: &Mutex<PromptTime>) -> Result<bool, Error> {
+	eprint!("proceed? [y/N] ");
+
+	let since = Instant::now();
+	prompted.lock().unwrap().since = Some(since);
+
+	let answer = task::spawn_blocking(|| {
+		let mut answer = String::new();
+		io::stdin().read_line(&mut answer).map(|_| answer)
+	})
+	.await;
+
+	let mut prompted = prompted.lock().unwrap();
+	prompted.since = None;
+	prompted.total += since.elapsed();
+
+	let answer = answer.map_err(io::Error::other)??;
+
+	Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
 /// Deploys an instance of the Beacon DAO using the details specified by the
-/// context.
-pub async fn deploy(ctx: Box<NewContext>) -> Result<Address, Error> {
+/// context. Progress is recorded in the state as it is made, and reported
+/// to the callback if one is given. Gives up with an error if the deployment
+/// doesn't finish within the context's timeout, not counting any time spent
+/// waiting for the plan to be approved.
+pub async fn deploy(
+	ctx: Box<NewContext>,
+	state: &Mutex<DeployState>,
+	events: Option<&dyn Fn(DeployEvent)>,
+) -> Result<Deployment, Error> {
+	// Wrapper for the API using the specified URL
+	let web3 = Web3::new(Http::new(ctx.eth_uri.as_ref())?);
+
+	log::debug!("connected to web3 API: {}", ctx.eth_uri);
+
+	let prompted = Mutex::new(PromptTime::default());
+	let Some(limit) = ctx.deploy_timeout else {
+		return deploy_unbounded(web3, ctx, state, events, &prompted).await;
+	};
+
+	let started = Instant::now();
+	let deployment = deploy_unbounded(web3, ctx, state, events, &prompted);
+	futures::pin_mut!(deployment);
+
+	// Prompting pushes the deadline back, so it's only up once it has passed
+	// without the deployer being prompted in the meantime
+	loop {
+		let deadline = prompted.lock().unwrap().extend(started + limit);
+		if let Some(res) = until(Some(deadline), &mut deployment).await {
+			return res;
+		}
+
+		if prompted.lock().unwrap().extend(started + limit) <= Instant::now() {
+			return Err(Error::Timeout(state.lock().unwrap().tx_hash));
+		}
+	}
+}
+
+/// Deploys an instance of the Beacon DAO, for as long as it takes. Time
+/// spent prompting the deployer is recorded.
+async fn deploy_unbounded<T: Transport>(
+	web3: Web3<T>,
+	ctx: Box<NewContext>,
+	state: &Mutex<DeployState>,
+	events: Option<&dyn Fn(DeployEvent)>,
+	prompted: &Mutex<PromptTime>,
+) -> Result<Deployment, Error> {
 	let NewContext {
 		private_key,
 		eth_uri,
 		eth_chain_id,
-		contracts_dir,
-		modules,
-		ipfs,
+		artifact,
+		name,
+		symbol,
+		supply,
+		metadata,
+		plan,
+		l2,
+		export_csv,
+		deployer,
+		recipient,
+		strict_cid,
+		constructor_args,
+		at_block,
+		gateway,
+		access_list,
+		confirmation,
+		output,
 		..
 	} = *ctx;
 
+	// Each phase's duration is logged, to show where the time goes
+	let started = Instant::now();
+	let emit = |event| {
+		if let Some(events) = events {
+			events(event);
+		}
+	};
+
+	let access_list = access_list.as_deref().map(read_access_list).transpose()?;
 	let secret_key =
 		SecretKey::from_str(private_key.as_str()).map_err(|e| Error::Serialization(Box::new(e)))?;
 	let ref_key = SecretKeyRef::new(&secret_key);
+	let from = ref_key.address();
 
-	// Wrapper for the API using the specified URL
-	let web3 = Web3::new(Http::new(eth_uri.as_ref())?);
-
-	log::debug!("connected to web3 API: {eth_uri}");
+	let resolver = AddressResolver::new(web3.transport().clone());
+	let recipient = resolve_parties(&resolver, from, deployer, recipient).await?;
 
 	// Load the source of the Idea.sol contract for deployment
-	let (src, DeployableContract { abi: _, bytecode }) = with_contract(contracts_dir)?;
+	let phase = Instant::now();
+	let (src, DeployableContract { abi: _, bytecode }) = with_contract(&artifact)?;
 
+	log::info!("loaded contract in {:?}", phase.elapsed());
 	log::debug!("loaded contract bytecode: {:?}", bytecode);
 	log::debug!("deploying metadata to IPFS");
 
-	// Deploy the metadata required for the contract, including specified
-	// payloads
-	let meta = deploy_metadata(&ipfs, DEFAULT_NAME, DEFAULT_DESCRIPTION, modules).await?;
-	let nonce = web3
-		.eth()
-		.transaction_count(ref_key.address(), None)
-		.await?;
+	// The chain state needed for deploying doesn't depend on the metadata, so
+	// it is checked while the metadata is uploaded
+	let upload = |metadata| async move {
+		let phase = Instant::now();
+		let meta = match metadata {
+			Metadata::Upload(ctx) => publish(*ctx).await?.cid_string,
+			Metadata::Existing(cid) => cid,
+		};
+		state.lock().unwrap().metadata_cid = Some(meta.clone());
 
-	log::info!("deployed metadata at: {:?}", meta);
+		log::info!("uploaded metadata in {:?}", phase.elapsed());
 
-	// Deploy an instance of the contract form the specified address
-	Ok(Contract::deploy(web3.eth(), src.as_slice())?
-		.confirmations(2)
-		.options(Options::with(|opt| {
-			opt.gas = Some(4_000_000.into());
-			opt.nonce = Some(nonce.into());
-		}))
-		.sign_with_key_and_execute(
-			bytecode.strip_prefix("0x").ok_or(Error::InvalidInput)?,
-			(
-				DEFAULT_NAME.to_owned(),
-				DEFAULT_SYMBOL.to_owned(),
-				DEFAULT_SUPPLY,
-				meta.cid_string,
-			),
-			ref_key,
-			Some(eth_chain_id),
-		)
-		.await?
-		.address())
-}
+		Ok::<_, Error>(meta)
+	};
+	let checks = async {
+		let phase = Instant::now();
+		let preflight = preflight(&web3, from, eth_chain_id, l2, at_block).await?;
 
-/// Gets a list of the addresses of contracts deployed using the context
-/// information.
-pub async fn list(
-	ListContext {
-		eth_uri,
-		contracts_dir,
-		private_key,
-		eth_chain_id: _,
-	}: ListContext,
-) -> Result<Vec<String>, Error> {
-	// Wrapper for the API using the specified URL
-	let web3 = Web3::new(Http::new(eth_uri.as_ref())?);
+		log::info!("estimated deployment cost in {:?}", phase.elapsed());
 
-	// Compare the bytecode of contracts deployed to the address with contracts
-	// located in contracts_dir
+		Ok::<_, Error>(preflight)
+	};
+
+	// Nothing may be uploaded until the plan is approved
 	let (
-		_,
-		DeployableContract {
-			abi: _,
-			bytecode: bc_hex,
+		meta,
+		Preflight {
+			nonce,
+			gas_price,
+			gas_limit,
+			..
 		},
-	) = with_contract(contracts_dir)?;
-	let bytecode = Bytes(hex::decode(bc_hex)?);
+	) = if plan {
+		let preflight = checks.await?;
+
+		let plan = plan_deployment(
+			&eth_uri,
+			eth_chain_id,
+			(&name, &symbol, supply),
+			&metadata,
+			&preflight,
+			&gateway,
+		)?;
+
+		// The plan goes to stderr alongside the prompt, keeping stdout for
+		// the deployment itself
+		match output {
+			OutputFormat::Text => eprint!("{plan}"),
+			OutputFormat::Json => eprintln!("{}", serde_json::to_string_pretty(&plan)?),
+		}
+
+		if !confirm(prompted).await? {
+			return Err(Error::Declined);
+		}
+
+		(upload(metadata).await?, preflight)
+	} else {
+		futures::try_join!(upload(metadata), checks)?
+	};
+
+	log::info!("deployed metadata at: {:?}", meta);
 
-	// Fetch transactions
-	let sender = web3
-		.parity_accounts()
-		.new_account_from_secret(
-			&H256::from_str(private_key.as_ref()).map_err(|_| Web3Error::Internal)?,
-			"",
+	emit(DeployEvent::MetadataUploaded { cid: meta.clone() });
+	emit(DeployEvent::GasLimitSet { gas_limit });
+
+	// Encode the constructor call creating an instance of the contract
+	let abi = ethabi::Contract::load(src.as_slice())?;
+	let expected = DaoDetails {
+		name,
+		symbol,
+		supply,
+		metadata_cid: meta.clone(),
+	};
+	let (data, mints_to_recipient) = encode_constructor(
+		&abi,
+		&bytecode,
+		&expected,
+		recipient.unwrap_or(from),
+		strict_cid,
+		constructor_args.as_deref(),
+	)?;
+
+	// Deploy an instance of the contract form the specified address
+	let phase = Instant::now();
+	let signed = web3
+		.accounts()
+		.sign_transaction(
+			TransactionParameters {
+				nonce: Some(nonce),
+				gas: gas_limit.into(),
+				gas_price: Some(gas_price),
+				data: Bytes(data),
+				chain_id: Some(eth_chain_id),
+				transaction_type: access_list.as_ref().map(|_| ACCESS_LIST_TX_TYPE.into()),
+				access_list,
+				..Default::default()
+			},
+			ref_key,
 		)
 		.await?;
+	let tx_hash = web3
+		.eth()
+		.send_raw_transaction(signed.raw_transaction)
+		.await?;
+	state.lock().unwrap().tx_hash = Some(tx_hash);
+	emit(DeployEvent::Broadcast { tx_hash });
 
-	let mut deployed = Vec::new();
-	let until = web3.eth().block_number().await?.as_u64();
+	log::info!(
+		"sent deployment transaction {:?} in {:?}",
+		tx_hash,
+		phase.elapsed()
+	);
 
-	// Iterate through blocks and look for transactions from the sender that
-	// create a contract, until the sender's balance is 0
-	for i in until..=0 {
-		if let Some(txs) = web3
-			.eth()
-			.block_with_txs(BlockId::Number(BlockNumber::Number(i.into())))
-			.await?
-			.map(|block| block.transactions)
-		{
-			let web3 = &web3;
+	// Wait for the transaction to be buried under enough blocks, or to stay
+	// in its block for long enough
+	let phase = Instant::now();
+	let eth = web3.eth();
+	let confirmations = match confirmation {
+		Some(Confirmation::Blocks(confirmations)) => confirmations,
+		_ => CONFIRMATIONS,
+	};
+	match confirmation {
+		Some(Confirmation::Elapsed(period)) => {
+			wait_for_stable_inclusion(&eth, tx_hash, period).await?;
+		}
+		_ => {
+			confirm::wait_for_confirmations(
+				web3.eth(),
+				web3.eth_filter(),
+				POLL_INTERVAL,
+				confirmations,
+				|| {
+					let eth = eth.clone();
 
-			// Look for transctions from me that have records containing the
-			// address of contracts deployed (receipts)
-			let receipts = stream::iter(txs.into_iter())
-				.then(async move |tx| {
-					web3.eth()
-						.transaction_receipt(tx.hash)
-						.await
-						.map(|v| v.map(|v| (tx, v)))
-				})
-				.filter_map(async move |v| v.ok())
-				.filter_map(async move |v| v)
-				.collect::<Vec<(Transaction, TransactionReceipt)>>()
-				.await;
+					async move {
+						Ok(eth
+							.transaction_receipt(tx_hash)
+							.await?
+							.and_then(|receipt| receipt.block_number))
+					}
+				},
+			)
+			.await?;
+		}
+	}
 
-			for (tx, receipt) in receipts {
-				// Check if the transaction deploys an instance of Idea contract
-				// if so, record the recipient, which is the created contract
-				if let Some(contract_addr) = receipt.contract_address && receipt.from == sender && tx.input == bytecode {
-                    deployed.push(contract_addr.to_string());
-                }
+	log::info!("confirmed deployment in {:?}", phase.elapsed());
+	log::info!("deployed in {:?} total", started.elapsed());
+
+	let Inclusion {
+		address,
+		block_number,
+		timestamp,
+		gas,
+	} = check_inclusion(&eth, tx_hash, gas_price).await?;
+
+	emit(DeployEvent::Confirmed { address });
+
+	// Echo back what the contract recorded, so that it can be verified
+	// against what was deployed. Contracts without the getters still
+	// deployed successfully
+	let contract = Contract::new(web3.eth(), address, abi);
+	let recorded =
+		match read_details(&contract).await {
+			Ok(recorded) => {
+				// Specified arguments needn't be the DAO's details
+				let mismatches = match constructor_args {
+					Some(_) => Vec::new(),
+					None => expected.mismatches(&recorded),
+				};
+				for field in mismatches {
+					eprintln!("warning: the DAO's contract recorded a different {field} than was deployed");
+				}
+
+				Some(recorded)
+			}
+			Err(e) => {
+				log::warn!("couldn't read back the DAO's details: {e}");
+
+				None
 			}
+		};
+
+	if let Some(recipient) = recipient.filter(|recipient| !mints_to_recipient && *recipient != from)
+	{
+		let receipt = contract
+			.signed_call_with_confirmations(
+				"transfer",
+				(recipient, supply),
+				Options::with(|opts| opts.gas_price = Some(gas_price)),
+				confirmations,
+				SecretKeyRef::new(&secret_key),
+			)
+			.await?;
 
-			continue;
+		if receipt.status.is_none_or(|status| status.is_zero()) {
+			return Err(Error::TransferFailed(receipt.transaction_hash));
 		}
 
-		break;
+		log::info!("transferred the supply to {:?}", recipient);
+	}
+
+	let deployment = Deployment {
+		address,
+		block_number,
+		tx_hash,
+		timestamp,
+		metadata_cid: Some(meta),
+		gas,
+		recorded,
+	};
+
+	if let Some(path) = export_csv {
+		export::write_csv(&path, eth_chain_id, slice::from_ref(&deployment), true)?;
 	}
 
-	Ok(deployed)
+	Ok(deployment)
+}
+
+/// Works out what deploying an instance of the Beacon DAO would do, without
+/// storing anything on IPFS or sending any transaction. The metadata's CID is
+/// computed rather than uploaded, and the deployment's gas is estimated
+/// against the latest block, unless another is specified.
+pub async fn simulate(ctx: Box<NewContext>) -> Result<Simulation, Error> {
+	let NewContext {
+		private_key,
+		eth_uri,
+		eth_chain_id,
+		artifact,
+		name,
+		symbol,
+		supply,
+		metadata,
+		l2,
+		deployer,
+		recipient,
+		strict_cid,
+		constructor_args,
+		at_block,
+		access_list,
+		..
+	} = *ctx;
+	let access_list = access_list.as_deref().map(read_access_list).transpose()?;
+
+	let from = key_address(&private_key)?;
+	let web3 = Web3::new(Http::new(eth_uri.as_ref())?);
+	let resolver = AddressResolver::new(web3.transport().clone());
+	let recipient = resolve_parties(&resolver, from, deployer, recipient).await?;
+	let (src, DeployableContract { abi: _, bytecode }) = with_contract(&artifact)?;
+
+	let hash = async {
+		match metadata {
+			Metadata::Upload(ctx) => Ok(publish(*ctx).await?.cid_string),
+			Metadata::Existing(cid) => Ok(cid),
+		}
+	};
+	let (meta, Preflight { gas_price, .. }) =
+		futures::try_join!(hash, preflight(&web3, from, eth_chain_id, l2, at_block))?;
+
+	let abi = ethabi::Contract::load(src.as_slice())?;
+	let expected = DaoDetails {
+		name,
+		symbol,
+		supply,
+		metadata_cid: meta,
+	};
+	let (data, _) = encode_constructor(
+		&abi,
+		&bytecode,
+		&expected,
+		recipient.unwrap_or(from),
+		strict_cid,
+		constructor_args.as_deref(),
+	)?;
+
+	let gas = web3
+		.eth()
+		.estimate_gas(
+			CallRequest {
+				from: Some(from),
+				gas_price: Some(gas_price),
+				data: Some(Bytes(data)),
+				transaction_type: access_list.as_ref().map(|_| ACCESS_LIST_TX_TYPE.into()),
+				access_list,
+				..Default::default()
+			},
+			at_block,
+		)
+		.await?;
+
+	Ok(Simulation {
+		deployer: from,
+		metadata_cid: expected.metadata_cid,
+		gas,
+		gas_price,
+		fee: gas * gas_price,
+	})
+}
+
+/// What deploying the Beacon DAO would do, according to a simulation.
+#[derive(Serialize)]
+pub struct Simulation {
+	pub(crate) deployer: Address,
+	pub(crate) metadata_cid: String,
+
+	// The estimated gas, and what it would cost at the current price in wei
+	pub(crate) gas: U256,
+	pub(crate) gas_price: U256,
+	pub(crate) fee: U256,
+}
+
+impl fmt::Display for Simulation {
+	fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(w, "deployer: {:?}", self.deployer)?;
+		writeln!(w, "metadata CID: {}", self.metadata_cid)?;
+		write!(
+			w,
+			"would use {} gas at {} wei, costing {} wei ({} in native units)",
+			self.gas,
+			self.gas_price,
+			self.fee,
+			native_units(self.fee)
+		)
+	}
+}
+
+/// Derives the address of the hex-encoded private key locally, since not
+/// every node can do it.
+fn key_address(private_key: &str) -> Result<Address, Error> {
+	let secret_key =
+		SecretKey::from_str(private_key).map_err(|e| Error::Serialization(Box::new(e)))?;
+
+	Ok(SecretKeyRef::new(&secret_key).address())
+}
+
+/// Checks that the private key belongs to the expected deployer, catching
+/// deployments from the wrong key before anything is spent, and resolves the
+/// address that the supply should go to.
+async fn resolve_parties<T: Transport>(
+	resolver: &AddressResolver<T>,
+	from: Address,
+	deployer: Option<String>,
+	recipient: Option<String>,
+) -> Result<Option<Address>, Error> {
+	if let Some(deployer) = deployer {
+		if resolver.resolve(&deployer).await? != from {
+			return Err(Error::DeployerMismatch(deployer, from));
+		}
+	}
+
+	match recipient {
+		Some(recipient) => Ok(Some(resolver.resolve(&recipient).await?)),
+		None => Ok(None),
+	}
+}
+
+/// Encodes the constructor call creating an instance of the contract with the
+/// details, returning whether the constructor mints the supply to the
+/// recipient itself.
+fn encode_constructor(
+	abi: &ethabi::Contract,
+	bytecode: &str,
+	expected: &DaoDetails,
+	recipient: Address,
+	strict_cid: bool,
+	args: Option<&[Value]>,
+) -> Result<(Vec<u8>, bool), Error> {
+	let code = hex::decode(bytecode.strip_prefix("0x").ok_or(Error::InvalidInput)?)?;
+	let constructor = abi.constructor().ok_or(Error::InvalidInput)?;
+
+	// Specified arguments are passed as-is, once they fit the ABI
+	if let Some(args) = args {
+		if args.len() != constructor.inputs.len() {
+			return Err(Error::ConstructorArgCount(
+				constructor.inputs.len(),
+				args.len(),
+			));
+		}
+
+		let params = constructor
+			.inputs
+			.iter()
+			.zip(args)
+			.map(|(param, arg)| coerce_arg(param, arg))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		return Ok((constructor.encode_input(code, &params)?, false));
+	}
+
+	let meta = &expected.metadata_cid;
+	let mut params = (
+		expected.name.clone(),
+		expected.symbol.clone(),
+		expected.supply,
+		meta.clone(),
+	)
+		.into_tokens();
+
+	// Contracts that store the CID in a fixed-size field cut off longer
+	// CID's, which leaves the DAO's metadata unreachable
+	if let Some(ParamType::FixedBytes(size)) = constructor.inputs.get(3).map(|param| &param.kind) {
+		if meta.len() > *size {
+			if strict_cid {
+				return Err(Error::CidTooLong(meta.clone(), *size));
+			}
+
+			eprintln!("warning: {}", Error::CidTooLong(meta.clone(), *size));
+		}
+
+		let mut stored = meta.clone().into_bytes();
+		stored.resize(*size, 0);
+		params[3] = Token::FixedBytes(stored);
+	}
+
+	// Versions of the contract that mint to a recipient take it last.
+	// Otherwise, the supply is transferred once the contract is deployed
+	let mints_to_recipient = constructor.inputs.len() == params.len() + 1
+		&& constructor.inputs.last().map(|param| &param.kind) == Some(&ParamType::Address);
+	if mints_to_recipient {
+		params.push(Token::Address(recipient));
+	}
+
+	Ok((constructor.encode_input(code, &params)?, mints_to_recipient))
+}
+
+/// Converts the JSON value to a token of the constructor parameter's type,
+/// failing with the parameter's name if it doesn't fit.
+fn coerce_arg(param: &ethabi::Param, arg: &Value) -> Result<Token, Error> {
+	let fits = |n: &U256, bits: usize| n.bits() <= bits;
+	let token = match (&param.kind, arg) {
+		(ParamType::Address, Value::String(addr)) => {
+			Address::from_str(addr).ok().map(Token::Address)
+		}
+		(ParamType::Uint(bits), Value::String(n)) => U256::from_dec_str(n)
+			.ok()
+			.filter(|n| fits(n, *bits))
+			.map(Token::Uint),
+		(ParamType::Uint(bits), Value::Number(n)) => n
+			.as_u64()
+			.map(U256::from)
+			.filter(|n| fits(n, *bits))
+			.map(Token::Uint),
+		(ParamType::String, Value::String(s)) => Some(Token::String(s.clone())),
+		(ParamType::Bool, Value::Bool(b)) => Some(Token::Bool(*b)),
+		_ => None,
+	};
+
+	token.ok_or_else(|| Error::InvalidConstructorArg(param.name.clone(), param.kind.to_string()))
+}
+
+/// Tracks how far a scan through the chain's blocks has gotten, drawing a
+/// progress bar to stderr if visible.
+struct ScanProgress {
+	total: u64,
+	scanned: u64,
+	found: usize,
+	visible: bool,
+}
+
+impl ScanProgress {
+	fn new(total: u64, visible: bool) -> Self {
+		Self {
+			total,
+			scanned: 0,
+			found: 0,
+			visible,
+		}
+	}
+
+	/// Records that a block was scanned, in which some contracts were found.
+	fn advance(&mut self, found: usize) {
+		self.scanned += 1;
+		self.found += found;
+
+		if self.visible {
+			eprint!("\r{}", self.render());
+		}
+	}
+
+	/// Moves past the progress bar once the scan is done.
+	fn finish(&self) {
+		if self.visible {
+			eprintln!();
+		}
+	}
+
+	/// Gets a line showing the blocks scanned out of the total, and the
+	/// contracts found so far.
+	fn render(&self) -> String {
+		let filled = (self.scanned * PROGRESS_WIDTH / self.total.max(1)) as usize;
+
+		format!(
+			"[{}{}] {}/{} blocks, {} found",
+			"#".repeat(filled),
+			" ".repeat(PROGRESS_WIDTH as usize - filled),
+			self.scanned,
+			self.total,
+			self.found
+		)
+	}
+}
+
+/// A deployed instance of the Beacon DAO.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Deployment {
+	pub(crate) address: Address,
+	pub(crate) block_number: u64,
+	pub(crate) tx_hash: H256,
+
+	// When the deployment's block was built, in seconds since the epoch
+	pub(crate) timestamp: u64,
+
+	// Only read from the contract if requested
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) metadata_cid: Option<String>,
+
+	// Only known for deployments made by this run
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) gas: Option<GasSummary>,
+
+	// The details read back from the contract after deploying it
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) recorded: Option<DaoDetails>,
+}
+
+/// The details of a DAO that its contract's constructor records.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DaoDetails {
+	pub(crate) name: String,
+	pub(crate) symbol: String,
+	pub(crate) supply: U256,
+	pub(crate) metadata_cid: String,
+}
+
+impl DaoDetails {
+	/// Names the details that differ from those that were recorded.
+	fn mismatches(&self, recorded: &Self) -> Vec<&'static str> {
+		[
+			("name", self.name == recorded.name),
+			("symbol", self.symbol == recorded.symbol),
+			("supply", self.supply == recorded.supply),
+			("metadata CID", self.metadata_cid == recorded.metadata_cid),
+		]
+		.into_iter()
+		.filter_map(|(field, matches)| (!matches).then_some(field))
+		.collect()
+	}
+}
+
+impl fmt::Display for DaoDetails {
+	fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			w,
+			"recorded name {}, symbol {}, supply {}, metadata {}",
+			self.name, self.symbol, self.supply, self.metadata_cid
+		)
+	}
+}
+
+/// What a deployment actually cost, according to its receipt.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GasSummary {
+	pub(crate) gas_used: U256,
+	pub(crate) effective_gas_price: U256,
+
+	// In wei
+	pub(crate) fee: U256,
+}
+
+impl GasSummary {
+	/// Summarizes the gas paid for the transaction, if its receipt reports
+	/// the gas used. Older nodes don't report the price that was actually
+	/// paid, in which case it's the price the transaction offered.
+	fn from_receipt(receipt: &TransactionReceipt, gas_price: U256) -> Option<Self> {
+		let effective_gas_price = receipt.effective_gas_price.unwrap_or(gas_price);
+
+		receipt.gas_used.map(|gas_used| Self {
+			gas_used,
+			effective_gas_price,
+			fee: gas_used * effective_gas_price,
+		})
+	}
+}
+
+impl fmt::Display for GasSummary {
+	fn fmt(&self, w: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			w,
+			"used {} gas at {} wei, costing {} wei ({} in native units)",
+			self.gas_used,
+			self.effective_gas_price,
+			self.fee,
+			native_units(self.fee)
+		)
+	}
+}
+
+/// Formats an amount of wei as a decimal amount of the chain's native token.
+fn native_units(wei: U256) -> String {
+	let unit = U256::exp10(18);
+	let frac = format!("{:0>18}", (wei % unit).to_string());
+
+	match frac.trim_end_matches('0') {
+		"" => (wei / unit).to_string(),
+		frac => format!("{}.{frac}", wei / unit),
+	}
+}
+
+/// How far a scan through the chain's blocks has gotten, recorded so that an
+/// interrupted scan can be resumed.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+	chain_id: u64,
+
+	// Blocks are scanned from the head down, so the scan is done once the
+	// next block passes zero
+	head: u64,
+	next: u64,
+
+	deployed: Vec<Deployment>,
+}
+
+impl Checkpoint {
+	/// Reads the checkpoint at the path, if one was recorded.
+	fn load(path: &str) -> Result<Option<Self>, Error> {
+		match OpenOptions::new().read(true).open(path) {
+			Ok(f) => Ok(Some(serde_json::from_reader(BufReader::new(f))?)),
+			Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	/// Reads the checkpoint to resume a scan of the chain from, if there's a
+	/// path to one and it was recorded.
+	fn resume(path: Option<&str>, chain_id: u64) -> Result<Option<Self>, Error> {
+		match path.map(Self::load).transpose()?.flatten() {
+			Some(prev) if prev.chain_id != chain_id => {
+				Err(Error::CheckpointMismatch(chain_id, prev.chain_id))
+			}
+			prev => Ok(prev),
+		}
+	}
+
+	/// Whether a checkpoint is due once the block has been scanned, which is
+	/// every CHECKPOINT_INTERVAL blocks down from the head. None is needed
+	/// once the last block is scanned, since the scan is done.
+	fn due(head: u64, block: u64) -> bool {
+		block > 0 && (head - block + 1).is_multiple_of(CHECKPOINT_INTERVAL)
+	}
+
+	/// Writes the checkpoint as JSON to the file at the path.
+	fn save(&self, path: &str) -> Result<(), Error> {
+		serde_json::to_writer_pretty(File::create(path)?, self)?;
+
+		Ok(())
+	}
+}
+
+/// Waits for the transaction to stay in the same block for the period,
+/// starting over whenever a reorg moves or drops it. Gets the number and hash
+/// of the block it stayed in.
+async fn wait_for_stable_inclusion<T: Transport>(
+	eth: &Eth<T>,
+	tx_hash: H256,
+	period: Duration,
+) -> Result<(U64, H256), Error> {
+	let mut included: Option<((U64, H256), Instant)> = None;
+
+	loop {
+		let block = eth
+			.transaction_receipt(tx_hash)
+			.await?
+			.and_then(|receipt| receipt.block_number.zip(receipt.block_hash));
+
+		included = match (included, block) {
+			(Some((prev, since)), Some(block)) if prev == block => {
+				if since.elapsed() >= period {
+					return Ok(prev);
+				}
+
+				Some((prev, since))
+			}
+			(prev, Some((number, hash))) => {
+				if prev.is_some() {
+					log::warn!("deployment was moved to block {:?} by a reorg", hash);
+				}
+
+				Some(((number, hash), Instant::now()))
+			}
+			(Some(_), None) => {
+				log::warn!(
+					"deployment was dropped by a reorg, waiting for it to be included again"
+				);
+
+				None
+			}
+			(None, None) => None,
+		};
+
+		// Check back once the period is up, or sooner to notice reorgs
+		let wait = included.map_or(POLL_INTERVAL, |(_, since)| {
+			period.saturating_sub(since.elapsed()).min(POLL_INTERVAL)
+		});
+		time::sleep(wait).await;
+	}
+}
+
+/// Where a confirmed deployment ended up, and what it cost.
+struct Inclusion {
+	address: Address,
+	block_number: u64,
+	timestamp: u64,
+	gas: Option<GasSummary>,
+}
+
+/// Checks that the confirmed deployment is still on the canonical chain. The
+/// receipt is fetched afresh, since a reorg can move the transaction to
+/// another block while waiting, which is harmless. A reorg deeper than the
+/// confirmations can still orphan the deployment though, leaving the block
+/// it was included in off the canonical chain and no code at its address.
+async fn check_inclusion<T: Transport>(
+	eth: &Eth<T>,
+	tx_hash: H256,
+	gas_price: U256,
+) -> Result<Inclusion, Error> {
+	let receipt = match eth.transaction_receipt(tx_hash).await? {
+		Some(TransactionReceipt {
+			status: Some(status),
+			..
+		}) if status.is_zero() => return Err(DeployError::ContractDeploymentFailure(tx_hash).into()),
+		Some(receipt) => receipt,
+
+		// The transaction was confirmed, so it can only have disappeared in a
+		// reorg
+		None => return Err(Error::ReorgedOut(tx_hash)),
+	};
+	let (address, block_number, block_hash) = match receipt {
+		TransactionReceipt {
+			contract_address: Some(address),
+			block_number: Some(number),
+			block_hash: Some(hash),
+			..
+		} => (address, number, hash),
+		TransactionReceipt {
+			contract_address: None,
+			..
+		} => return Err(DeployError::ContractDeploymentFailure(tx_hash).into()),
+
+		// Dropped back into the mempool
+		_ => return Err(Error::ReorgedOut(tx_hash)),
+	};
+
+	let (block, code) = futures::try_join!(
+		eth.block(BlockId::Number(BlockNumber::Number(block_number))),
+		eth.code(address, None)
+	)?;
+	match block {
+		Some(block) if block.hash == Some(block_hash) && !code.0.is_empty() => Ok(Inclusion {
+			address,
+			block_number: block_number.as_u64(),
+			timestamp: block.timestamp.as_u64(),
+			gas: GasSummary::from_receipt(&receipt, gas_price),
+		}),
+		_ => Err(Error::ReorgedOut(tx_hash)),
+	}
+}
+
+/// Waits for the future until the deadline if there is one, giving nothing if
+/// the deadline passes first.
+async fn until<F: Future>(deadline: Option<Instant>, fut: F) -> Option<F::Output> {
+	match deadline {
+		Some(deadline) => time::timeout(deadline.saturating_duration_since(Instant::now()), fut)
+			.await
+			.ok(),
+		None => Some(fut.await),
+	}
+}
+
+/// Gets a stream of the contracts deployed using the context information,
+/// newest first, reading each one's metadata CID if requested. Contracts on
+/// the page are yielded as soon as they're found, followed by an error if the
+/// scan fails.
+pub fn list(ctx: ListContext) -> impl stream::Stream<Item = Result<Deployment, Error>> {
+	let (tx, rx) = mpsc::unbounded();
+	let scanned = async move {
+		// Wrapper for the API using the specified URL
+		let web3 = Web3::new(Http::new(ctx.eth_uri.as_ref())?);
+
+		scan(web3, ctx, tx).await
+	};
+
+	// The scan only yields its error, once it's done. Contracts already
+	// found are handed over first
+	let failure = stream::once(scanned).filter_map(|res| future::ready(res.err().map(Err)));
+
+	stream::select_with_strategy(rx.map(Ok), failure, |_: &mut ()| PollNext::Left)
+}
+
+/// Scans the chain for the contracts deployed using the context information,
+/// sending those on the page to the channel as soon as they're found.
+async fn scan<T: Transport>(
+	web3: Web3<T>,
+	ListContext {
+		artifact,
+		private_key,
+		eth_chain_id,
+		quiet,
+		checkpoint,
+		scan_concurrency,
+		limit,
+		offset,
+		list_timeout,
+		strict,
+		resolve_metadata,
+		export_csv,
+		..
+	}: ListContext,
+	deployments: UnboundedSender<Deployment>,
+) -> Result<(), Error> {
+	// Compare the bytecode of contracts deployed to the address with contracts
+	// located in the artifact
+	let (
+		abi,
+		DeployableContract {
+			abi: _,
+			bytecode: bc_hex,
+		},
+	) = with_contract(&artifact)?;
+	let bytecode = Bytes(hex::decode(bc_hex.trim_start_matches("0x"))?);
+
+	// The sender is derived from the key locally, since not every node
+	// can do it
+	let sender = key_address(&private_key)?;
+
+	// Pick up where an interrupted scan left off. Blocks built since then
+	// aren't scanned
+	let (head, next, mut deployed) = match Checkpoint::resume(checkpoint.as_deref(), eth_chain_id)?
+	{
+		Some(prev) => {
+			log::info!("resuming scan from block {}", prev.next);
+
+			(prev.head, prev.next, prev.deployed)
+		}
+		None => {
+			let head = web3.eth().block_number().await?.as_u64();
+
+			(head, head, Vec::new())
+		}
+	};
+
+	// Contracts on the page are reported as soon as they're found, which for
+	// a resumed scan includes those found before it was interrupted. They're
+	// kept for the CSV export. Deployments are found newest first, so pages
+	// are stable as long as nothing is deployed between them
+	let web3 = &web3;
+	let abi = &abi;
+	let mut page = Vec::new();
+	let mut report = async |from: usize, deployed: &[Deployment]| {
+		for deployment in page_slice(deployed, from, offset, limit) {
+			let mut deployment = deployment.clone();
+			if resolve_metadata {
+				deployment.metadata_cid = Some(metadata_cid(web3, abi, deployment.address).await?);
+			}
+
+			page.push(deployment.clone());
+
+			// Nobody is left to hand the contract to if the stream was dropped
+			let _ = deployments.unbounded_send(deployment);
+		}
+
+		Ok::<_, Error>(())
+	};
+	report(0, &deployed).await?;
+
+	// Only draw progress for people watching
+	let mut progress = ScanProgress::new(head + 1, !quiet && io::stderr().is_terminal());
+	progress.scanned = head - next;
+	progress.found = deployed.len();
+
+	// Blocks are fetched ahead of the scan, but at most scan_concurrency at a
+	// time, and handed over in order
+	let mut blocks = stream::iter((0..=next).rev())
+		.map(|i| async move {
+			let block = web3
+				.eth()
+				.block_with_txs(BlockId::Number(BlockNumber::Number(i.into())))
+				.await?;
+			let (timestamp, txs) = match block {
+				Some(block) => (block.timestamp.as_u64(), block.transactions),
+				None => return Ok::<_, Error>((i, None)),
+			};
+
+			// Look for transctions from me that have records containing the
+			// address of contracts deployed (receipts)
+			let receipts = stream::iter(txs)
+				.then(async move |tx| {
+					web3.eth()
+						.transaction_receipt(tx.hash)
+						.await
+						.map(|v| v.map(|v| (tx, v)))
+				})
+				.filter_map(async move |v| v.ok())
+				.filter_map(async move |v| v)
+				.collect::<Vec<(Transaction, TransactionReceipt)>>()
+				.await;
+
+			Ok((i, Some((timestamp, receipts))))
+		})
+		.buffered(scan_concurrency);
+
+	// Iterate through blocks and look for transactions from the sender that
+	// create a contract, until the sender's balance is 0. Blocks still being
+	// fetched are dropped once one is missing
+	let deadline = list_timeout.map(|limit| Instant::now() + limit);
+	let mut resume = next;
+	let mut timed_out = false;
+	loop {
+		let (i, block) = match until(deadline, blocks.try_next()).await {
+			Some(fetched) => match fetched? {
+				Some(fetched) => fetched,
+				None => break,
+			},
+			None => {
+				timed_out = true;
+				break;
+			}
+		};
+
+		if let Some((timestamp, receipts)) = block {
+			let found = deployed.len();
+
+			for (tx, receipt) in receipts {
+				// Check if the transaction deploys an instance of Idea contract
+				// if so, record the recipient, which is the created contract
+				let address = receipt
+					.contract_address
+					.filter(|_| receipt.from == sender && tx.input == bytecode);
+
+				if let Some(address) = address {
+					deployed.push(Deployment {
+						address,
+						block_number: i,
+						tx_hash: tx.hash,
+						timestamp,
+						metadata_cid: None,
+						gas: None,
+						recorded: None,
+					});
+				}
+			}
+
+			progress.advance(deployed.len() - found);
+			report(found, &deployed).await?;
+			resume = i.saturating_sub(1);
+
+			if let Some(path) = checkpoint.as_deref().filter(|_| Checkpoint::due(head, i)) {
+				Checkpoint {
+					chain_id: eth_chain_id,
+					head,
+					next: i - 1,
+					deployed: deployed.clone(),
+				}
+				.save(path)?;
+			}
+
+			// Older blocks can't change the page once it is full
+			if !page_full(deployed.len(), offset, limit) {
+				continue;
+			}
+		}
+
+		break;
+	}
+
+	// Stop fetching blocks that won't be scanned
+	drop(blocks);
+	progress.finish();
+
+	if timed_out {
+		// Record where the scan stopped, so that it can be finished later
+		if let Some(path) = checkpoint.as_deref() {
+			Checkpoint {
+				chain_id: eth_chain_id,
+				head,
+				next: resume,
+				deployed: deployed.clone(),
+			}
+			.save(path)?;
+		}
+
+		if strict {
+			return Err(Error::ScanTimedOut(resume));
+		}
+
+		eprintln!(
+			"warning: {}, so only the contracts found so far are listed",
+			Error::ScanTimedOut(resume)
+		);
+	} else {
+		// The next scan should start from the new head
+		match checkpoint.map(fs::remove_file) {
+			Some(Err(e)) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+			_ => {}
+		}
+	}
+
+	if let Some(path) = export_csv {
+		export::write_csv(&path, eth_chain_id, &page, false)?;
+	}
+
+	Ok(())
+}
+
+/// Gets the deployments on the page of the limit starting at the offset,
+/// among those found so far, skipping those before `from` that were already
+/// reported.
+fn page_slice(
+	deployed: &[Deployment],
+	from: usize,
+	offset: usize,
+	limit: Option<usize>,
+) -> &[Deployment] {
+	let end = limit.map_or(deployed.len(), |limit| {
+		offset.saturating_add(limit).min(deployed.len())
+	});
+
+	&deployed[from.max(offset).min(end)..end]
+}
+
+/// Whether the page has all the deployments it can show once this many have
+/// been found.
+fn page_full(found: usize, offset: usize, limit: Option<usize>) -> bool {
+	limit.is_some_and(|limit| found >= offset.saturating_add(limit))
+}
+
+/// Reads the CID of the metadata that the deployed DAO was created with.
+pub(crate) async fn metadata_cid<T: Transport>(
+	web3: &Web3<T>,
+	abi: &[u8],
+	address: Address,
+) -> Result<String, Error> {
+	let contract = Contract::from_json(web3.eth(), address, abi)?;
+
+	Ok(contract
+		.query(METADATA_GETTER, (), None, Options::default(), None)
+		.await?)
+}
+
+/// Reads back the details that the DAO's contract recorded when it was
+/// deployed.
+async fn read_details<T: Transport>(contract: &Contract<T>) -> Result<DaoDetails, Error> {
+	let (name, symbol, supply, metadata_cid) = futures::try_join!(
+		contract.query("name", (), None, Options::default(), None),
+		contract.query("symbol", (), None, Options::default(), None),
+		contract.query("totalSupply", (), None, Options::default(), None),
+		contract.query(METADATA_GETTER, (), None, Options::default(), None),
+	)?;
+
+	Ok(DaoDetails {
+		name,
+		symbol,
+		supply,
+		metadata_cid,
+	})
+}
+
+/// Lists the modules installed in the DAO specified by the context.
+pub async fn modules(
+	ModulesContext { target, ipfs, .. }: ModulesContext,
+) -> Result<Vec<InstalledModule>, Error> {
+	let cid = match target {
+		ModulesTarget::Metadata(cid) => cid,
+		ModulesTarget::Contract {
+			address,
+			eth_uri,
+			artifact,
+		} => {
+			let web3 = Web3::new(Http::new(eth_uri.as_ref())?);
+			let (abi, _) = with_contract(&artifact)?;
+
+			metadata_cid(&web3, &abi, address).await?
+		}
+	};
+
+	log::debug!("reading modules from metadata {cid}");
+
+	payload::read_modules(&ipfs, &cid).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		test_support::{capture_logs, fake_server, logged, temp_dir, MockTransport},
+		wasm::ModuleOptions,
+	};
+	use ipfs_api::{IpfsClient, TryFromUri};
+	use serde_json::json;
+	use std::thread;
+	use web3::types::{Block, Transaction};
+
+	#[test]
+	fn prefixes_artifact_bytecode() {
+		for bytecode in ["6080", "0x6080"] {
+			let artifact = json!({ "abi": [], "bytecode": bytecode });
+			let parsed = parse_artifact(artifact, ArtifactFormat::Combined, None).unwrap();
+
+			assert_eq!(parsed.bytecode, "0x6080");
+		}
+	}
+
+	#[test]
+	fn counts_scanned_blocks() {
+		let mut progress = ScanProgress::new(4, false);
+		progress.advance(0);
+		progress.advance(2);
+		progress.advance(1);
+
+		assert_eq!(progress.scanned, 3);
+		assert_eq!(progress.found, 3);
+	}
+
+	#[test]
+	fn renders_progress() {
+		let mut progress = ScanProgress::new(4, false);
+		assert!(progress.render().ends_with("] 0/4 blocks, 0 found"));
+
+		progress.advance(1);
+		progress.advance(0);
+		let line = progress.render();
+
+		assert!(line.ends_with("] 2/4 blocks, 1 found"));
+		assert_eq!(line.matches('#').count() as u64, PROGRESS_WIDTH / 2);
+		assert_eq!(line.find(']'), Some(PROGRESS_WIDTH as usize + 1));
+	}
+
+	#[test]
+	fn renders_empty_scans() {
+		// A chain with no blocks to scan can't divide by zero
+		let progress = ScanProgress::new(0, false);
+
+		assert!(progress.render().ends_with("] 0/0 blocks, 0 found"));
+	}
+
+	#[test]
+	fn names_malformed_artifact_fields() {
+		let cases = [
+			(json!({ "abi": [] }), ("bytecode", "present")),
+			(
+				json!({ "abi": [], "bytecode": 1 }),
+				("bytecode", "a hex string"),
+			),
+			(
+				json!({ "abi": [], "bytecode": "0xzz" }),
+				("bytecode", "a hex string"),
+			),
+			(json!({ "bytecode": "0x" }), ("abi", "present")),
+			(json!({ "abi": {}, "bytecode": "0x" }), ("abi", "an array")),
+		];
+
+		for (artifact, expected) in cases {
+			assert!(
+				matches!(
+					parse_artifact(artifact, ArtifactFormat::Combined, None),
+					Err(Error::InvalidArtifact(field, why)) if (field, why) == expected
+				),
+				"{expected:?}"
+			);
+		}
+
+		// Foundry keeps its bytecode in an object
+		assert!(matches!(
+			parse_artifact(
+				json!({ "abi": [], "bytecode": "0x6080" }),
+				ArtifactFormat::Foundry,
+				None
+			),
+			Err(Error::InvalidArtifact("bytecode.object", "present"))
+		));
+	}
+
+	fn artifact_at(dir: &Path, path: Option<&str>, format: Option<ArtifactFormat>) -> Artifact {
+		Artifact {
+			contracts_dir: dir.display().to_string(),
+			path: path.map(str::to_owned),
+			bytecode_file: None,
+			format,
+		}
+	}
+
+	#[test]
+	fn reports_missing_artifact_paths() {
+		let dir = temp_dir("missing-artifact");
+		let path = dir.join("Idea.json").display().to_string();
+
+		let err = read_artifact(&artifact_at(&dir, Some("Idea.json"), None)).unwrap_err();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(matches!(&err, Error::MissingArtifact(p, _) if *p == path));
+		assert!(err.to_string().contains(&path), "{err}");
+	}
+
+	#[test]
+	fn locates_specified_artifacts() {
+		let dir = Path::new("contracts");
+
+		let (path, format) = locate_artifact(&artifact_at(dir, Some("Idea.json"), None)).unwrap();
+		assert_eq!(path, dir.join("Idea.json"));
+		assert!(format.is_none());
+
+		let (path, format) =
+			locate_artifact(&artifact_at(dir, None, Some(ArtifactFormat::Foundry))).unwrap();
+		assert_eq!(path, dir.join("out/Idea.sol/Idea.json"));
+		assert!(matches!(format, Some(ArtifactFormat::Foundry)));
+	}
+
+	#[test]
+	fn locates_absolute_artifacts() {
+		let dir = temp_dir("absolute-artifact");
+		let path = dir.join("Custom.json");
+		fs::write(&path, r#"{ "abi": [] }"#).unwrap();
+
+		// An absolute path is used as is, wherever the contracts are
+		let artifact = artifact_at(
+			Path::new("contracts"),
+			Some(path.to_str().unwrap()),
+			Some(ArtifactFormat::Hardhat),
+		);
+		let (located, format, src) = read_artifact(&artifact).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(located, path);
+		assert!(matches!(format, Some(ArtifactFormat::Hardhat)));
+		assert_eq!(src, json!({ "abi": [] }));
+	}
+
+	#[test]
+	fn prefers_bytecode_from_elsewhere() {
+		let artifact = json!({ "abi": [] });
+		let parsed =
+			parse_artifact(artifact, ArtifactFormat::Combined, Some("0x60".to_owned())).unwrap();
+
+		assert_eq!(parsed.bytecode, "0x60");
+	}
+
+	#[test]
+	fn reads_bytecode_files() {
+		let dir = temp_dir("bytecode");
+		let path = dir.join("Idea.bin").display().to_string();
+
+		fs::write(&path, "6080\n").unwrap();
+		assert_eq!(read_bytecode(&path).unwrap(), "0x6080");
+
+		fs::write(&path, "not hex").unwrap();
+		assert!(matches!(read_bytecode(&path), Err(Error::InvalidBytecode(p)) if p == path));
+
+		fs::remove_dir_all(&dir).unwrap();
+		assert!(matches!(
+			read_bytecode(&path),
+			Err(Error::MissingBytecodeFile(p, _)) if p == path
+		));
+	}
+
+	#[test]
+	fn parses_each_artifact_format() {
+		let combined = json!({ "abi": [], "bytecode": "0x6080" });
+		let foundry = json!({ "abi": [], "bytecode": { "object": "0x6080" } });
+
+		for (artifact, format) in [
+			(combined.clone(), ArtifactFormat::Combined),
+			(combined, ArtifactFormat::Hardhat),
+			(foundry, ArtifactFormat::Foundry),
+		] {
+			let parsed = parse_artifact(artifact, format, None).unwrap();
+
+			assert_eq!(parsed.bytecode, "0x6080");
+			assert_eq!(parsed.abi, json!([]));
+		}
+	}
+
+	/// Writes an empty artifact at the format's conventional path.
+	fn write_artifact(dir: &Path, format: ArtifactFormat) {
+		let path = dir.join(format.default_path());
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, "{}").unwrap();
+	}
+
+	#[test]
+	fn detects_artifact_formats() {
+		let dir = temp_dir("locate");
+		let artifact = artifact_at(&dir, None, None);
+
+		// Nothing built yet is reported at the combined path
+		let (path, format) = locate_artifact(&artifact).unwrap();
+		assert_eq!(path, dir.join(ArtifactFormat::Combined.default_path()));
+		assert!(matches!(format, Some(ArtifactFormat::Combined)));
+
+		write_artifact(&dir, ArtifactFormat::Hardhat);
+		let (path, format) = locate_artifact(&artifact).unwrap();
+		assert_eq!(path, dir.join(ArtifactFormat::Hardhat.default_path()));
+		assert!(matches!(format, Some(ArtifactFormat::Hardhat)));
+
+		write_artifact(&dir, ArtifactFormat::Foundry);
+		let res = locate_artifact(&artifact);
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(matches!(res, Err(Error::AmbiguousArtifact(paths)) if paths.len() == 2));
+	}
+
+	#[test]
+	fn budgets_fees_per_l2() {
+		let gas_price = U256::from(10);
+
+		assert_eq!(
+			fees(gas_price, None),
+			(GAS_LIMIT, U256::from(GAS_LIMIT * 10))
+		);
+		assert_eq!(
+			fees(gas_price, Some(L2::Arbitrum)),
+			(
+				GAS_LIMIT * ARBITRUM_GAS_LIMIT_MULTIPLIER,
+				U256::from(GAS_LIMIT * ARBITRUM_GAS_LIMIT_MULTIPLIER * 10)
+			)
+		);
+		assert_eq!(
+			fees(gas_price, Some(L2::Optimism)),
+			(
+				GAS_LIMIT,
+				U256::from(GAS_LIMIT * 10 * (100 + OPTIMISM_L1_FEE_PERCENT) / 100)
+			)
+		);
+	}
+
+	#[test]
+	fn formats_native_units() {
+		assert_eq!(native_units(U256::zero()), "0");
+		assert_eq!(native_units(U256::exp10(18)), "1");
+		assert_eq!(native_units(U256::exp10(18) * 3 / 2), "1.5");
+		assert_eq!(native_units(U256::one()), "0.000000000000000001");
+		assert_eq!(native_units(U256::exp10(21) + 20), "1000.00000000000000002");
+	}
+
+	#[test]
+	fn summarizes_receipt_gas() {
+		let receipt = TransactionReceipt {
+			gas_used: Some(U256::from(21_000)),
+			effective_gas_price: Some(U256::from(2_000_000_000u64)),
+			..Default::default()
+		};
+		let gas = GasSummary::from_receipt(&receipt, U256::from(3_000_000_000u64)).unwrap();
+
+		assert_eq!(gas.effective_gas_price, U256::from(2_000_000_000u64));
+		assert_eq!(gas.fee, U256::from(42_000_000_000_000u64));
+		assert_eq!(
+			gas.to_string(),
+			"used 21000 gas at 2000000000 wei, costing 42000000000000 wei (0.000042 in native units)"
+		);
+
+		// Falls back on the offered price, and needs the gas used
+		let legacy = TransactionReceipt {
+			effective_gas_price: None,
+			..receipt.clone()
+		};
+		assert_eq!(
+			GasSummary::from_receipt(&legacy, U256::from(3_000_000_000u64))
+				.unwrap()
+				.fee,
+			U256::from(63_000_000_000_000u64)
+		);
+		assert!(GasSummary::from_receipt(
+			&TransactionReceipt {
+				gas_used: None,
+				..receipt
+			},
+			U256::one()
+		)
+		.is_none());
+	}
+
+	#[test]
+	fn names_mismatched_details() {
+		let details = DaoDetails {
+			name: "Vision DAO".to_owned(),
+			symbol: "VIS".to_owned(),
+			supply: U256::from(100),
+			metadata_cid: "bafy".to_owned(),
+		};
+
+		assert!(details.mismatches(&details.clone()).is_empty());
+		assert_eq!(
+			details.mismatches(&DaoDetails {
+				symbol: "VSN".to_owned(),
+				metadata_cid: "bafz".to_owned(),
+				..details.clone()
+			}),
+			["symbol", "metadata CID"]
+		);
+		assert_eq!(
+			details.mismatches(&DaoDetails {
+				name: String::new(),
+				supply: U256::zero(),
+				..details.clone()
+			}),
+			["name", "supply"]
+		);
+	}
+
+	fn param(name: &str, kind: ParamType) -> ethabi::Param {
+		ethabi::Param {
+			name: name.to_owned(),
+			kind,
+			internal_type: None,
+		}
+	}
+
+	#[test]
+	fn coerces_fitting_args() {
+		let owner = "0x1111111111111111111111111111111111111111";
+
+		assert_eq!(
+			coerce_arg(&param("owner", ParamType::Address), &json!(owner)).unwrap(),
+			Token::Address(Address::repeat_byte(0x11))
+		);
+		assert_eq!(
+			coerce_arg(&param("supply", ParamType::Uint(256)), &json!("1000")).unwrap(),
+			Token::Uint(U256::from(1000))
+		);
+		assert_eq!(
+			coerce_arg(&param("supply", ParamType::Uint(8)), &json!(255)).unwrap(),
+			Token::Uint(U256::from(255))
+		);
+		assert_eq!(
+			coerce_arg(&param("name", ParamType::String), &json!("DAO")).unwrap(),
+			Token::String("DAO".to_owned())
+		);
+		assert_eq!(
+			coerce_arg(&param("open", ParamType::Bool), &json!(true)).unwrap(),
+			Token::Bool(true)
+		);
+	}
+
+	#[test]
+	fn rejects_mismatched_args() {
+		let cases = [
+			// Malformed values of the right JSON type
+			(param("owner", ParamType::Address), json!("0x11")),
+			(param("supply", ParamType::Uint(256)), json!("-1")),
+			(param("supply", ParamType::Uint(256)), json!(1.5)),
+			// Values too big for the parameter
+			(param("supply", ParamType::Uint(8)), json!(256)),
+			(param("supply", ParamType::Uint(8)), json!("256")),
+			// The wrong JSON type
+			(param("name", ParamType::String), json!(1)),
+			(param("open", ParamType::Bool), json!("true")),
+			// Types that can't be specified
+			(param("data", ParamType::Bytes), json!("0x")),
+		];
+
+		for (param, arg) in cases {
+			assert!(
+				matches!(
+					coerce_arg(&param, &arg),
+					Err(Error::InvalidConstructorArg(name, kind))
+						if name == param.name && kind == param.kind.to_string()
+				),
+				"{} {arg}",
+				param.name
+			);
+		}
+	}
+
+	#[test]
+	fn derives_key_addresses() {
+		// The first of the accounts that Hardhat and Anvil fund
+		let key = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+		assert_eq!(
+			key_address(key).unwrap(),
+			Address::from_str("0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266").unwrap()
+		);
+		assert!(matches!(key_address("0x12"), Err(Error::Serialization(_))));
+	}
+
+	/// Gets the receipt of a successful deployment in the block.
+	fn deployed_receipt(block_number: u64, block_hash: H256) -> Value {
+		serde_json::to_value(TransactionReceipt {
+			transaction_hash: H256::repeat_byte(0x22),
+			block_number: Some(block_number.into()),
+			block_hash: Some(block_hash),
+			contract_address: Some(Address::repeat_byte(0x11)),
+			status: Some(1.into()),
+			gas_used: Some(21_000.into()),
+			..Default::default()
+		})
+		.unwrap()
+	}
+
+	fn block_with_hash(hash: H256) -> Value {
+		serde_json::to_value(Block::<H256> {
+			hash: Some(hash),
+			timestamp: 1_700_000_000.into(),
+			..Default::default()
+		})
+		.unwrap()
+	}
+
+	#[actix_rt::test]
+	async fn accepts_deployments_moved_by_reorgs() {
+		let transport = MockTransport::default();
+		let moved_to = H256::repeat_byte(0xbb);
+
+		// First seen in block 10, then re-included in block 11 before the
+		// confirmations were up
+		transport
+			.respond("eth_getTransactionReceipt", deployed_receipt(11, moved_to))
+			.respond("eth_getBlockByNumber", block_with_hash(moved_to))
+			.respond("eth_getCode", json!("0x6080"));
+		let eth = Web3::new(transport.clone()).eth();
+		let inclusion = check_inclusion(&eth, H256::repeat_byte(0x22), U256::one())
+			.await
+			.unwrap();
+
+		assert_eq!(inclusion.address, Address::repeat_byte(0x11));
+		assert_eq!(inclusion.block_number, 11);
+		assert_eq!(inclusion.timestamp, 1_700_000_000);
+		assert_eq!(transport.calls("eth_getBlockByNumber")[0][0], json!("0xb"));
+	}
+
+	#[actix_rt::test]
+	async fn reports_orphaned_deployments() {
+		let transport = MockTransport::default();
+
+		// The block the node still reports is no longer canonical, and the
+		// contract's code is gone
+		transport
+			.respond(
+				"eth_getTransactionReceipt",
+				deployed_receipt(10, H256::repeat_byte(0xaa)),
+			)
+			.respond(
+				"eth_getBlockByNumber",
+				block_with_hash(H256::repeat_byte(0xcc)),
+			)
+			.respond("eth_getCode", json!("0x"));
+		let eth = Web3::new(transport).eth();
+
+		assert!(matches!(
+			check_inclusion(&eth, H256::repeat_byte(0x22), U256::one()).await,
+			Err(Error::ReorgedOut(tx_hash)) if tx_hash == H256::repeat_byte(0x22)
+		));
+	}
+
+	#[actix_rt::test]
+	async fn reports_dropped_deployments() {
+		let transport = MockTransport::default();
+		transport.respond("eth_getTransactionReceipt", Value::Null);
+		let eth = Web3::new(transport).eth();
+
+		assert!(matches!(
+			check_inclusion(&eth, H256::repeat_byte(0x22), U256::one()).await,
+			Err(Error::ReorgedOut(_))
+		));
+	}
+
+	#[test]
+	fn describes_deployment_plans() {
+		let preflight = Preflight {
+			nonce: U256::zero(),
+			gas_price: U256::from(10),
+			gas_limit: 3_000_000,
+			cost: U256::from(30_000_000),
+		};
+		let plan = plan_deployment(
+			"http://localhost:8545",
+			1337,
+			("Vision DAO", "VIS", U256::from(100)),
+			&Metadata::Existing("bafy".to_owned()),
+			&preflight,
+			"https://ipfs.io/",
+		)
+		.unwrap();
+
+		assert_eq!(
+			plan.to_string(),
+			"deployment plan:
+  chain: 1337 via http://localhost:8545
+  DAO: Vision DAO (VIS), with a supply of 100
+  metadata: existing https://ipfs.io/ipfs/bafy
+  gas: limit of 3000000 at 10 wei, costing up to 30000000 wei
+"
+		);
+		assert_eq!(
+			serde_json::to_value(&plan).unwrap(),
+			json!({
+				"eth_uri": "http://localhost:8545",
+				"eth_chain_id": 1337,
+				"name": "Vision DAO",
+				"symbol": "VIS",
+				"supply": "0x64",
+				"metadata": { "existing": { "link": "https://ipfs.io/ipfs/bafy" } },
+				"gas_limit": 3_000_000,
+				"gas_price": "0xa",
+				"cost": "0x1c9c380",
+			})
+		);
+	}
+
+	#[test]
+	fn extends_deadlines_by_prompt_time() {
+		let deadline = Instant::now();
+		let prompted = PromptTime {
+			since: None,
+			total: Duration::from_secs(5),
+		};
+		assert_eq!(prompted.extend(deadline), deadline + Duration::from_secs(5));
+
+		// Time spent on a prompt that's still open counts too
+		let prompting = PromptTime {
+			since: Some(Instant::now() - Duration::from_secs(3)),
+			..prompted
+		};
+		assert!(prompting.extend(deadline) >= deadline + Duration::from_secs(8));
+	}
+
+	/// Gets an ABI whose constructor takes parameters of the types.
+	fn constructor_abi(kinds: &[&str]) -> ethabi::Contract {
+		let inputs: Vec<_> = kinds
+			.iter()
+			.enumerate()
+			.map(|(i, kind)| json!({ "name": format!("arg{i}"), "type": kind }))
+			.collect();
+		let abi =
+			json!([{ "type": "constructor", "inputs": inputs, "stateMutability": "nonpayable" }]);
+
+		ethabi::Contract::load(abi.to_string().as_bytes()).unwrap()
+	}
+
+	fn details_with_cid(metadata_cid: &str) -> DaoDetails {
+		DaoDetails {
+			name: "Vision DAO".to_owned(),
+			symbol: "VIS".to_owned(),
+			supply: U256::from(100),
+			metadata_cid: metadata_cid.to_owned(),
+		}
+	}
+
+	/// Encodes a constructor call the way the contract expects it.
+	fn constructor_call(abi: &ethabi::Contract, params: &[Token]) -> Vec<u8> {
+		abi.constructor()
+			.unwrap()
+			.encode_input(vec![0x60, 0x80], params)
+			.unwrap()
+	}
+
+	#[test]
+	fn mints_to_recipients_if_the_constructor_can() {
+		let details = details_with_cid("bafy");
+		let recipient = Address::repeat_byte(0x33);
+		let mut params = (
+			details.name.clone(),
+			details.symbol.clone(),
+			details.supply,
+			details.metadata_cid.clone(),
+		)
+			.into_tokens();
+
+		let transferring = constructor_abi(&["string", "string", "uint256", "string"]);
+		let (data, mints) =
+			encode_constructor(&transferring, "0x6080", &details, recipient, false, None).unwrap();
+		assert!(!mints);
+		assert_eq!(data, constructor_call(&transferring, &params));
+
+		let minting = constructor_abi(&["string", "string", "uint256", "string", "address"]);
+		let (data, mints) =
+			encode_constructor(&minting, "0x6080", &details, recipient, false, None).unwrap();
+		params.push(Token::Address(recipient));
+		assert!(mints);
+		assert_eq!(data, constructor_call(&minting, &params));
+	}
+
+	#[test]
+	fn warns_of_cids_too_long_to_store() {
+		let abi = constructor_abi(&["string", "string", "uint256", "bytes32"]);
+		let recipient = Address::repeat_byte(0x33);
+		let params = |stored: &str| {
+			let mut stored = stored.as_bytes().to_vec();
+			stored.resize(32, 0);
+
+			vec![
+				Token::String("Vision DAO".to_owned()),
+				Token::String("VIS".to_owned()),
+				Token::Uint(U256::from(100)),
+				Token::FixedBytes(stored),
+			]
+		};
+
+		// Short CID's are padded to fit
+		let short = details_with_cid("QmShort");
+		let (data, _) = encode_constructor(&abi, "0x6080", &short, recipient, true, None).unwrap();
+		assert_eq!(data, constructor_call(&abi, &params("QmShort")));
+
+		// Longer ones are cut off, which is only an error under --strict-cid
+		let cid = "bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua";
+		let long = details_with_cid(cid);
+		assert!(matches!(
+			encode_constructor(&abi, "0x6080", &long, recipient, true, None),
+			Err(Error::CidTooLong(c, 32)) if c == cid
+		));
+
+		let (data, _) = encode_constructor(&abi, "0x6080", &long, recipient, false, None).unwrap();
+		assert_eq!(data, constructor_call(&abi, &params(&cid[..32])));
+	}
+
+	fn deployments(n: u64) -> Vec<Deployment> {
+		(0..n)
+			.map(|i| Deployment {
+				address: Address::from_low_u64_be(i),
+				block_number: 100 - i,
+				tx_hash: H256::from_low_u64_be(i),
+				timestamp: 1_700_000_000,
+				metadata_cid: None,
+				gas: None,
+				recorded: None,
+			})
+			.collect()
+	}
+
+	fn block_numbers(page: &[Deployment]) -> Vec<u64> {
+		page.iter()
+			.map(|deployment| deployment.block_number)
+			.collect()
+	}
+
+	#[test]
+	fn slices_pages() {
+		let found = deployments(5);
+
+		assert_eq!(
+			block_numbers(page_slice(&found, 0, 0, None)),
+			[100, 99, 98, 97, 96]
+		);
+		assert_eq!(block_numbers(page_slice(&found, 0, 1, Some(2))), [99, 98]);
+
+		// Pages past what's been found are empty, or cut short
+		assert!(page_slice(&found, 0, 5, Some(2)).is_empty());
+		assert_eq!(block_numbers(page_slice(&found, 0, 4, Some(2))), [96]);
+
+		// Deployments that were already reported are skipped
+		assert_eq!(block_numbers(page_slice(&found, 2, 1, Some(3))), [98, 97]);
+		assert!(page_slice(&found, 5, 0, None).is_empty());
+	}
+
+	#[test]
+	fn fills_pages() {
+		assert!(!page_full(100, 0, None));
+		assert!(!page_full(2, 1, Some(2)));
+		assert!(page_full(3, 1, Some(2)));
+		assert!(page_full(4, 1, Some(2)));
+	}
+
+	#[test]
+	fn checkpoints_every_interval() {
+		let head = 250;
+		let due: Vec<_> = (0..=head)
+			.rev()
+			.filter(|&i| Checkpoint::due(head, i))
+			.collect();
+
+		assert_eq!(due, [151, 51]);
+		assert!(!Checkpoint::due(99, 0));
+	}
+
+	#[test]
+	fn resumes_from_checkpoints() {
+		let dir = temp_dir("checkpoint");
+		let path = dir.join("scan.json").display().to_string();
+
+		// Nothing to resume yet
+		assert!(Checkpoint::resume(None, 1).unwrap().is_none());
+		assert!(Checkpoint::resume(Some(&path), 1).unwrap().is_none());
+
+		Checkpoint {
+			chain_id: 1,
+			head: 250,
+			next: 150,
+			deployed: deployments(2),
+		}
+		.save(&path)
+		.unwrap();
+
+		let prev = Checkpoint::resume(Some(&path), 1).unwrap().unwrap();
+		assert_eq!((prev.head, prev.next), (250, 150));
+		assert_eq!(block_numbers(&prev.deployed), [100, 99]);
+
+		// A checkpoint from another chain can't be resumed
+		let res = Checkpoint::resume(Some(&path), 5);
+		fs::remove_dir_all(&dir).unwrap();
+		assert!(matches!(res, Err(Error::CheckpointMismatch(5, 1))));
+	}
+
+	#[test]
+	fn serializes_listed_deployments() {
+		let mut found = deployments(2);
+		found[1].metadata_cid = Some("QmMetadata".to_owned());
+
+		assert_eq!(
+			serde_json::to_value(&found).unwrap(),
+			json!([
+				{
+					"address": "0x0000000000000000000000000000000000000000",
+					"block_number": 100,
+					"tx_hash": format!("{:?}", H256::zero()),
+					"timestamp": 1_700_000_000,
+				},
+				{
+					"address": "0x0000000000000000000000000000000000000001",
+					"block_number": 99,
+					"tx_hash": format!("{:?}", H256::from_low_u64_be(1)),
+					"timestamp": 1_700_000_000,
+					"metadata_cid": "QmMetadata",
+				},
+			])
+		);
+	}
+
+	#[actix_rt::test]
+	async fn checks_balances_at_pinned_blocks() {
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_chainId", json!("0x1"))
+			.respond("eth_getTransactionCount", json!("0x0"))
+			.respond("eth_gasPrice", json!("0x1"))
+			.respond("eth_getBalance", json!("0xffffffffffff"));
+		let web3 = Web3::new(transport.clone());
+		let from = Address::from_low_u64_be(1);
+
+		preflight(&web3, from, 1, None, None).await.unwrap();
+		preflight(&web3, from, 1, None, Some(BlockNumber::Number(123.into())))
+			.await
+			.unwrap();
+
+		let blocks: Vec<_> = transport
+			.calls("eth_getBalance")
+			.into_iter()
+			.map(|params| params[1].clone())
+			.collect();
+		assert_eq!(blocks, [json!("latest"), json!("0x7b")]);
+
+		// The nonce is always read from the latest state
+		assert!(transport
+			.calls("eth_getTransactionCount")
+			.iter()
+			.all(|params| params[1] == json!("latest")));
+	}
+
+	/// The first of the keys that Hardhat and Anvil fund.
+	const KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+	/// Writes a combined artifact of the DAO's contract, taking its details
+	/// in its constructor, to the directory.
+	fn write_dao_artifact(dir: &Path) {
+		let abi = json!([{
+			"type": "constructor",
+			"inputs": [
+				{ "name": "name", "type": "string" },
+				{ "name": "symbol", "type": "string" },
+				{ "name": "supply", "type": "uint256" },
+				{ "name": "metadataCid", "type": "string" },
+			],
+			"stateMutability": "nonpayable",
+		}]);
+		let path = dir.join(ArtifactFormat::Combined.default_path());
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(
+			path,
+			json!({ "abi": abi, "bytecode": "0x6080" }).to_string(),
+		)
+		.unwrap();
+	}
+
+	/// Gets the context for deploying the DAO with existing metadata, using
+	/// the contract built in the directory.
+	fn new_context(dir: &Path) -> Box<NewContext> {
+		write_dao_artifact(dir);
+
+		Box::new(NewContext {
+			private_key: KEY.to_owned(),
+			eth_uri: "http://localhost:8545".to_owned(),
+			eth_chain_id: 1,
+			artifact: artifact_at(dir, None, None),
+			name: "Vision DAO".to_owned(),
+			symbol: "VIS".to_owned(),
+			supply: U256::from(100),
+			metadata: Metadata::Existing("QmMetadata".to_owned()),
+			state_file: None,
+			deploy_timeout: None,
+			plan: false,
+			l2: None,
+			export_csv: None,
+			deployer: None,
+			recipient: None,
+			strict_cid: false,
+			constructor_args: None,
+			simulate_only: false,
+			at_block: None,
+			gateway: "https://ipfs.io".to_owned(),
+			access_list: None,
+			confirmation: Some(Confirmation::Elapsed(Duration::ZERO)),
+			output: OutputFormat::Text,
+		})
+	}
+
+	/// Gets a chain that a deployment from new_context succeeds on.
+	fn deployable_chain() -> MockTransport {
+		let block_hash = H256::repeat_byte(0xaa);
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_chainId", json!("0x1"))
+			.respond("eth_getTransactionCount", json!("0x0"))
+			.respond("eth_gasPrice", json!("0x1"))
+			.respond("eth_getBalance", json!("0xffffffffffff"))
+			.respond("eth_sendRawTransaction", json!(H256::repeat_byte(0x22)))
+			.respond(
+				"eth_getTransactionReceipt",
+				deployed_receipt(10, block_hash),
+			)
+			.respond("eth_getBlockByNumber", block_with_hash(block_hash))
+			.respond("eth_getCode", json!("0x6080"));
+
+		transport
+	}
+
+	#[actix_rt::test]
+	async fn reports_deployment_milestones() {
+		let dir = temp_dir("milestones");
+		let state = Mutex::new(DeployState::default());
+		let events = Mutex::new(Vec::new());
+		let report = |event| events.lock().unwrap().push(format!("{event:?}"));
+		let deployment = deploy_unbounded(
+			Web3::new(deployable_chain()),
+			new_context(&dir),
+			&state,
+			Some(&report),
+			&Mutex::default(),
+		)
+		.await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(deployment.unwrap().address, Address::repeat_byte(0x11));
+		assert_eq!(
+			*events.lock().unwrap(),
+			[
+				r#"MetadataUploaded { cid: "QmMetadata" }"#.to_owned(),
+				format!("GasLimitSet {{ gas_limit: {GAS_LIMIT} }}"),
+				format!("Broadcast {{ tx_hash: {:?} }}", H256::repeat_byte(0x22)),
+				format!("Confirmed {{ address: {:?} }}", Address::repeat_byte(0x11)),
+			]
+		);
+		assert_eq!(state.lock().unwrap().tx_hash, Some(H256::repeat_byte(0x22)));
+	}
+
+	#[actix_rt::test]
+	async fn checks_the_chain_while_uploading() {
+		let dir = temp_dir("pipelined");
+
+		// The node takes far longer to store the metadata than the chain
+		// takes to answer
+		let ipfs_uri = fake_server(|_| {
+			thread::sleep(Duration::from_secs(5));
+
+			(200, "{}".to_owned())
+		});
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_chainId", json!("0x5"))
+			.respond("eth_getTransactionCount", json!("0x0"))
+			.respond("eth_gasPrice", json!("0x1"))
+			.respond("eth_getBalance", json!("0xffffffffffff"));
+
+		let mut ctx = new_context(&dir);
+		ctx.metadata = Metadata::Upload(Box::new(MetadataContext {
+			title: "Vision DAO".to_owned(),
+			description: String::new(),
+			compress_description: false,
+			icon: None,
+			schema_version: None,
+			modules: Vec::new(),
+			module_options: ModuleOptions::default(),
+			ipfs: IpfsClient::from_str(&ipfs_uri).unwrap(),
+			ipfs_uri,
+			ipfs_handle: None,
+			upload_cache: None,
+			keep_ipfs_alive: false,
+			hash_only: false,
+			print_metadata: false,
+			replication_check: None,
+			require_replication: false,
+			check_ipfs_storage: false,
+		}));
+
+		let started = Instant::now();
+		let res = deploy_unbounded(
+			Web3::new(transport),
+			ctx,
+			&Mutex::default(),
+			None,
+			&Mutex::default(),
+		)
+		.await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		// The wrong chain is reported without waiting for the upload
+		assert!(matches!(res, Err(Error::ChainIdMismatch(1, id)) if id == 5.into()));
+		assert!(started.elapsed() < Duration::from_secs(5));
+	}
+
+	#[actix_rt::test]
+	async fn times_out_deployments() {
+		let dir = temp_dir("deploy-timeout");
+		let mut ctx = new_context(&dir);
+
+		// The node never answers in time
+		ctx.eth_uri = fake_server(|_| {
+			thread::sleep(Duration::from_secs(5));
+
+			(200, "{}".to_owned())
+		});
+		ctx.deploy_timeout = Some(Duration::from_millis(100));
+
+		let started = Instant::now();
+		let res = deploy(ctx, &Mutex::default(), None).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(matches!(res, Err(Error::Timeout(None))));
+		assert!(started.elapsed() < Duration::from_secs(5));
+	}
+
+	#[actix_rt::test]
+	async fn waits_until_deadlines() {
+		let soon = Instant::now() + Duration::from_millis(10);
+
+		assert_eq!(until(Some(soon), future::ready(1)).await, Some(1));
+		assert_eq!(until(None, future::ready(1)).await, Some(1));
+		assert_eq!(until(Some(soon), future::pending::<()>()).await, None);
+	}
+
+	#[actix_rt::test]
+	async fn logs_phase_timings() {
+		capture_logs();
+		let dir = temp_dir("phases");
+		let res = deploy_unbounded(
+			Web3::new(deployable_chain()),
+			new_context(&dir),
+			&Mutex::default(),
+			None,
+			&Mutex::default(),
+		)
+		.await;
+		fs::remove_dir_all(&dir).unwrap();
+		res.unwrap();
+
+		let logged = logged();
+		for phase in [
+			"loaded contract in ",
+			"uploaded metadata in ",
+			"estimated deployment cost in ",
+			"sent deployment transaction ",
+			"confirmed deployment in ",
+		] {
+			assert!(logged.iter().any(|msg| msg.starts_with(phase)), "{phase}");
+		}
+		assert!(logged
+			.iter()
+			.any(|msg| msg.starts_with("deployed in ") && msg.ends_with(" total")));
+	}
+
+	/// Gets the context for listing the DAO's deployed with the contract
+	/// built in the directory.
+	fn list_context(dir: &Path) -> ListContext {
+		write_dao_artifact(dir);
+
+		ListContext {
+			private_key: KEY.to_owned(),
+			eth_uri: "http://localhost:8545".to_owned(),
+			eth_chain_id: 1,
+			artifact: artifact_at(dir, None, None),
+			quiet: true,
+			checkpoint: None,
+			scan_concurrency: 4,
+			limit: None,
+			offset: 0,
+			list_timeout: None,
+			strict: false,
+			output: OutputFormat::Text,
+			stream: false,
+			resolve_metadata: false,
+			export_csv: None,
+		}
+	}
+
+	/// Gets a chain of the blocks down from the head, in which the DAO was
+	/// deployed by KEY's account in those listed. Blocks are fetched newest
+	/// first.
+	fn chain_with_deployments(head: u64, deployed_in: &[u64]) -> MockTransport {
+		let transport = MockTransport::default();
+		transport.respond("eth_blockNumber", json!(format!("{head:#x}")));
+
+		for i in (0..=head).rev() {
+			respond_with_block(&transport, i, deployed_in.contains(&i));
+		}
+
+		transport
+	}
+
+	/// Queues up the block with the number for the next fetch, in which the
+	/// DAO was deployed by KEY's account if specified.
+	fn respond_with_block(transport: &MockTransport, i: u64, deployed: bool) {
+		let tx = Transaction {
+			hash: H256::from_low_u64_be(i),
+			input: Bytes(vec![0x60, 0x80]),
+			..Default::default()
+		};
+		let block = Block {
+			number: Some(i.into()),
+			timestamp: 1_700_000_000.into(),
+			transactions: if deployed { vec![tx] } else { Vec::new() },
+			..Default::default()
+		};
+		transport.respond("eth_getBlockByNumber", serde_json::to_value(block).unwrap());
+
+		if deployed {
+			let receipt = TransactionReceipt {
+				transaction_hash: H256::from_low_u64_be(i),
+				from: key_address(KEY).unwrap(),
+				contract_address: Some(Address::from_low_u64_be(i)),
+				status: Some(1.into()),
+				..Default::default()
+			};
+			transport.respond(
+				"eth_getTransactionReceipt",
+				serde_json::to_value(receipt).unwrap(),
+			);
+		}
+	}
+
+	/// Scans the chain, getting the result and the blocks of the deployments
+	/// reported along the way.
+	async fn scan_chain(
+		transport: MockTransport,
+		ctx: ListContext,
+	) -> (Result<(), Error>, Vec<u64>) {
+		let (tx, rx) = mpsc::unbounded();
+		let res = scan(Web3::new(transport), ctx, tx).await;
+		let found: Vec<_> = rx.collect().await;
+
+		(res, block_numbers(&found))
+	}
+
+	#[actix_rt::test]
+	async fn scans_blocks_concurrently_in_order() {
+		let dir = temp_dir("scan-workers");
+		let transport = chain_with_deployments(20, &[18, 3]);
+		let (res, found) = scan_chain(transport.clone(), list_context(&dir)).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		res.unwrap();
+		assert_eq!(found, [18, 3]);
+		assert_eq!(transport.calls("eth_getBlockByNumber").len(), 21);
+	}
+
+	#[actix_rt::test]
+	async fn fetches_blocks_at_most_a_batch_ahead() {
+		let dir = temp_dir("scan-backpressure");
+		let transport = chain_with_deployments(20, &[20]);
+		let mut ctx = list_context(&dir);
+		ctx.limit = Some(1);
+		let (res, found) = scan_chain(transport.clone(), ctx).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		// The page is full after the first block, by when no more than the
+		// workers' worth of blocks were fetched past it
+		res.unwrap();
+		assert_eq!(found, [20]);
+		assert!(transport.calls("eth_getBlockByNumber").len() <= 1 + 4);
+	}
+
+	#[test]
+	fn reads_access_lists() {
+		let dir = temp_dir("access-list");
+		let path = dir.join("access.json");
+		let key = H256::repeat_byte(0x01);
+		fs::write(
+			&path,
+			json!([{ "address": Address::repeat_byte(0x11), "storageKeys": [key] }]).to_string(),
+		)
+		.unwrap();
+		let list = read_access_list(&path.display().to_string());
+
+		fs::write(&path, r#"[{ "address": "0x11" }]"#).unwrap();
+		let malformed = read_access_list(&path.display().to_string());
+		fs::remove_dir_all(&dir).unwrap();
+
+		let list = list.unwrap();
+		assert_eq!(list.len(), 1);
+		assert_eq!(list[0].address, Address::repeat_byte(0x11));
+		assert_eq!(list[0].storage_keys, [key]);
+		assert!(matches!(malformed, Err(Error::InvalidAccessList(..))));
+	}
+
+	#[actix_rt::test]
+	async fn sends_access_list_transactions() {
+		let dir = temp_dir("access-list-deploy");
+		let path = dir.join("access.json");
+		fs::write(
+			&path,
+			json!([{ "address": Address::repeat_byte(0x11), "storageKeys": [] }]).to_string(),
+		)
+		.unwrap();
+
+		let transport = deployable_chain();
+		let mut ctx = new_context(&dir);
+		ctx.access_list = Some(path.display().to_string());
+		let res = deploy_unbounded(
+			Web3::new(transport.clone()),
+			ctx,
+			&Mutex::default(),
+			None,
+			&Mutex::default(),
+		)
+		.await;
+		fs::remove_dir_all(&dir).unwrap();
+		res.unwrap();
+
+		// EIP-2930 transactions are prefixed by their type, unlike legacy
+		// ones
+		let sent = &transport.calls("eth_sendRawTransaction")[0][0];
+		assert!(sent.as_str().unwrap().starts_with("0x01"));
+	}
+
+	/// Gets a node that takes far too long to answer anything.
+	fn stalled_chain() -> Web3<Http> {
+		let uri = fake_server(|_| {
+			thread::sleep(Duration::from_secs(5));
+
+			(200, "{}".to_owned())
+		});
+
+		Web3::new(Http::new(&uri).unwrap())
+	}
+
+	#[actix_rt::test]
+	async fn stops_scans_at_the_timeout() {
+		let dir = temp_dir("list-timeout");
+		let path = dir.join("scan.json").display().to_string();
+		Checkpoint {
+			chain_id: 1,
+			head: 250,
+			next: 150,
+			deployed: deployments(2),
+		}
+		.save(&path)
+		.unwrap();
+
+		let mut ctx = list_context(&dir);
+		ctx.checkpoint = Some(path.clone());
+		ctx.list_timeout = Some(Duration::from_millis(100));
+		let (tx, rx) = mpsc::unbounded();
+		let res = scan(stalled_chain(), ctx, tx).await;
+		let found: Vec<_> = rx.collect().await;
+		let saved = Checkpoint::load(&path).unwrap().unwrap();
+
+		// A strict scan fails instead of listing what it found
+		let mut ctx = list_context(&dir);
+		ctx.checkpoint = Some(path);
+		ctx.list_timeout = Some(Duration::from_millis(100));
+		ctx.strict = true;
+		let (tx, _rx) = mpsc::unbounded();
+		let strict = scan(stalled_chain(), ctx, tx).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		res.unwrap();
+		assert_eq!(block_numbers(&found), [100, 99]);
+		assert_eq!((saved.head, saved.next), (250, 150));
+		assert!(matches!(strict, Err(Error::ScanTimedOut(150))));
+	}
+
+	#[test]
+	fn reads_abis_as_built() {
+		let dir = temp_dir("abi");
+		let abi =
+			json!([{ "type": "function", "name": "metadataCid", "inputs": [], "outputs": [] }]);
+		let path = dir.join(ArtifactFormat::Foundry.default_path());
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(
+			path,
+			json!({ "abi": abi, "bytecode": { "object": "0x6080" } }).to_string(),
+		)
+		.unwrap();
+
+		let read = super::abi(AbiContext {
+			artifact: artifact_at(&dir, None, None),
+		});
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(read.unwrap(), abi);
+	}
+
+	#[actix_rt::test]
+	async fn waits_for_deployments_to_stay_put() {
+		let (first, second) = (H256::repeat_byte(0xaa), H256::repeat_byte(0xbb));
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_getTransactionReceipt", deployed_receipt(10, first))
+			.respond("eth_getTransactionReceipt", deployed_receipt(11, second))
+			.respond("eth_getTransactionReceipt", deployed_receipt(11, second));
+		let eth = Web3::new(transport.clone()).eth();
+
+		// Moving the deployment starts the period over, until it's seen in
+		// the same block twice
+		let included = wait_for_stable_inclusion(&eth, H256::repeat_byte(0x22), Duration::ZERO)
+			.await
+			.unwrap();
+
+		assert_eq!(included, (11.into(), second));
+		assert_eq!(transport.calls("eth_getTransactionReceipt").len(), 3);
+	}
+
+	#[actix_rt::test]
+	async fn lists_only_the_keys_deployments() {
+		let dir = temp_dir("scan-sender");
+		let transport = chain_with_deployments(5, &[4]);
+
+		// The second of the accounts that Hardhat and Anvil fund didn't
+		// deploy anything. Its address is derived without asking the node
+		let mut ctx = list_context(&dir);
+		ctx.private_key =
+			"59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d".to_owned();
+		let (res, found) = scan_chain(transport.clone(), ctx).await;
+		let (mine, found_mine) =
+			scan_chain(chain_with_deployments(5, &[4]), list_context(&dir)).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		res.unwrap();
+		mine.unwrap();
+		assert!(found.is_empty());
+		assert_eq!(found_mine, [4]);
+		assert!(transport.calls("personal_importRawKey").is_empty());
+	}
+
+	#[actix_rt::test]
+	async fn streams_deployments_before_failing() {
+		let dir = temp_dir("scan-stream");
+
+		// The node gives back garbage for the block after the deployment
+		let transport = MockTransport::default();
+		transport.respond("eth_blockNumber", json!("0x5"));
+		respond_with_block(&transport, 5, true);
+		transport.respond("eth_getBlockByNumber", json!(42));
+		let mut ctx = list_context(&dir);
+		ctx.scan_concurrency = 1;
+		let (res, found) = scan_chain(transport, ctx).await;
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert!(res.is_err());
+		assert_eq!(found, [5]);
+	}
+
+	#[test]
+	fn reports_artifact_build_details() {
+		let dir = temp_dir("version");
+		let path = dir.join(ArtifactFormat::Combined.default_path());
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+		// Combined artifacts record solc's metadata as a JSON string
+		let metadata = json!({
+			"compiler": { "version": "0.8.19+commit.7dd6d404" },
+			"settings": { "optimizer": { "enabled": true, "runs": 200 } },
+		});
+		fs::write(
+			&path,
+			json!({ "abi": [], "bytecode": "0x6080", "metadata": metadata.to_string() })
+				.to_string(),
+		)
+		.unwrap();
+		let built = version(VersionContext {
+			artifact: Some(artifact_at(&dir, None, None)),
+			output: OutputFormat::Text,
+		});
+
+		fs::write(
+			&path,
+			json!({ "abi": [], "bytecode": "0x6080" }).to_string(),
+		)
+		.unwrap();
+		let unrecorded = version(VersionContext {
+			artifact: Some(artifact_at(&dir, None, None)),
+			output: OutputFormat::Text,
+		});
+		fs::remove_dir_all(&dir).unwrap();
+
+		let daowiz = format!("daowiz {}", env!("CARGO_PKG_VERSION"));
+		let path = path.display();
+		assert_eq!(
+			built.unwrap().to_string(),
+			format!("{daowiz}\nartifact: {path}\ncompiler: 0.8.19+commit.7dd6d404\noptimizer: enabled, 200 runs")
+		);
+		assert_eq!(
+			unrecorded.unwrap().to_string(),
+			format!("{daowiz}\nartifact: {path}\ncompiler: unknown\noptimizer: unknown")
+		);
+
+		let bare = version(VersionContext {
+			artifact: None,
+			output: OutputFormat::Json,
+		});
+		assert_eq!(
+			serde_json::to_value(bare.unwrap()).unwrap(),
+			json!({ "daowiz": env!("CARGO_PKG_VERSION") })
+		);
+	}
 }
@@ -0,0 +1,210 @@
+use ipfs_api::IpfsApi;
+use web3::{api::Web3, transports::Http};
+
+use super::{
+	super::cli::DoctorContext,
+	contract::{locate_artifact, with_contract},
+	error::Error,
+};
+
+/// The outcome of one of the checks run by `doctor`.
+pub struct Check {
+	pub(crate) name: &'static str,
+
+	// A description of what was found if the check passed, or why it failed
+	pub(crate) result: Result<String, Error>,
+}
+
+/// Checks that the configured RPC node, IPFS node, and contracts are usable
+/// for deploying a Beacon DAO.
+pub async fn diagnose(
+	DoctorContext {
+		eth_uri,
+		eth_chain_id,
+		artifact,
+		ipfs,
+	}: DoctorContext,
+) -> Vec<Check> {
+	let rpc = async {
+		let chain_id = Web3::new(Http::new(eth_uri.as_ref())?)
+			.eth()
+			.chain_id()
+			.await?;
+
+		if chain_id != eth_chain_id.into() {
+			return Err(Error::ChainIdMismatch(eth_chain_id, chain_id));
+		}
+
+		Ok(format!("{eth_uri} is on chain {chain_id}"))
+	};
+	let ipfs = async {
+		let version = ipfs.version().await?;
+
+		Ok(format!("IPFS node is running version {}", version.version))
+	};
+	let contracts = async {
+		with_contract(&artifact)?;
+
+		Ok(format!(
+			"loaded the Idea contract from {}",
+			locate_artifact(&artifact)?.0.display()
+		))
+	};
+
+	let (rpc, ipfs, contracts) = futures::join!(rpc, ipfs, contracts);
+
+	vec![
+		Check {
+			name: "rpc",
+			result: rpc,
+		},
+		Check {
+			name: "ipfs",
+			result: ipfs,
+		},
+		Check {
+			name: "contracts",
+			result: contracts,
+		},
+	]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		cli::{Artifact, ArtifactFormat},
+		test_support::{fake_node, fake_server, missing_node, temp_dir},
+	};
+	use ipfs_api::IpfsClient;
+	use std::{fs, path::Path};
+
+	const VERSION: &str = r#"{"Version":"0.29.0","Commit":"","Repo":"15"}"#;
+
+	/// Gets the URL of a fake RPC node on chain 1337.
+	fn rpc_node() -> String {
+		fake_server(|_| {
+			(
+				200,
+				r#"{"jsonrpc":"2.0","id":0,"result":"0x539"}"#.to_owned(),
+			)
+		})
+	}
+
+	/// Gets the contracts dir, with a built contract in it if asked.
+	fn contracts_dir(name: &str, built: bool) -> Artifact {
+		let dir = temp_dir(name);
+		if built {
+			let path = dir.join(ArtifactFormat::Combined.default_path());
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, r#"{ "abi": [], "bytecode": "0x6080" }"#).unwrap();
+		}
+
+		Artifact {
+			contracts_dir: dir.display().to_string(),
+			path: None,
+			bytecode_file: None,
+			format: None,
+		}
+	}
+
+	async fn diagnose_with(
+		eth_uri: String,
+		eth_chain_id: u64,
+		ipfs: IpfsClient,
+		artifact: Artifact,
+	) -> Vec<(&'static str, Result<String, Error>)> {
+		diagnose(DoctorContext {
+			eth_uri,
+			eth_chain_id,
+			artifact,
+			ipfs,
+		})
+		.await
+		.into_iter()
+		.map(|Check { name, result }| (name, result))
+		.collect()
+	}
+
+	#[actix_rt::test]
+	async fn passes_working_setups() {
+		let checks = diagnose_with(
+			rpc_node(),
+			1337,
+			fake_node(200, VERSION),
+			contracts_dir("doctor-pass", true),
+		)
+		.await;
+
+		assert_eq!(
+			checks.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+			["rpc", "ipfs", "contracts"]
+		);
+		assert!(checks[0].1.as_ref().unwrap().ends_with("is on chain 1337"));
+		assert_eq!(
+			checks[1].1.as_ref().unwrap(),
+			"IPFS node is running version 0.29.0"
+		);
+		assert!(checks[2].1.is_ok());
+	}
+
+	#[actix_rt::test]
+	async fn fails_unreachable_rpc_nodes() {
+		let checks = diagnose_with(
+			"http://127.0.0.1:1".to_owned(),
+			1337,
+			fake_node(200, VERSION),
+			contracts_dir("doctor-rpc", true),
+		)
+		.await;
+
+		assert!(checks[0].1.is_err());
+		assert!(checks[1].1.is_ok() && checks[2].1.is_ok());
+	}
+
+	#[actix_rt::test]
+	async fn fails_mismatched_chain_ids() {
+		let checks = diagnose_with(
+			rpc_node(),
+			1,
+			fake_node(200, VERSION),
+			contracts_dir("doctor-chain", true),
+		)
+		.await;
+
+		assert!(matches!(
+			&checks[0].1,
+			Err(Error::ChainIdMismatch(1, chain_id)) if *chain_id == 1337.into()
+		));
+	}
+
+	#[actix_rt::test]
+	async fn fails_unreachable_ipfs_nodes() {
+		let checks = diagnose_with(
+			rpc_node(),
+			1337,
+			missing_node(),
+			contracts_dir("doctor-ipfs", true),
+		)
+		.await;
+
+		assert!(matches!(checks[1].1, Err(Error::Ipfs(_))));
+		assert!(checks[0].1.is_ok() && checks[2].1.is_ok());
+	}
+
+	#[actix_rt::test]
+	async fn fails_missing_contracts() {
+		let artifact = contracts_dir("doctor-contracts", false);
+		let expected = Path::new(&artifact.contracts_dir)
+			.join(ArtifactFormat::Combined.default_path())
+			.display()
+			.to_string();
+		let checks = diagnose_with(rpc_node(), 1337, fake_node(200, VERSION), artifact).await;
+
+		assert!(matches!(
+			&checks[2].1,
+			Err(Error::MissingArtifact(path, _)) if *path == expected
+		));
+		assert!(checks[0].1.is_ok() && checks[1].1.is_ok());
+	}
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, str::FromStr, sync::Mutex};
+use web3::{
+	api::Namespace,
+	contract::{ens::Ens, Error as QueryError},
+	types::Address,
+	Transport,
+};
+
+use super::error::Error;
+
+/// Resolves the values of address flags, which may be either hex addresses
+/// or ENS names. Names are only looked up once per run.
+pub struct AddressResolver<T: Transport> {
+	ens: Ens<T>,
+	resolved: Mutex<HashMap<String, Address>>,
+}
+
+impl<T: Transport> AddressResolver<T> {
+	/// Creates a resolver looking up names on the chain of the transport.
+	pub fn new(transport: T) -> Self {
+		Self {
+			ens: Ens::new(transport),
+			resolved: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Gets the address specified by the hex address or ENS name.
+	pub async fn resolve(&self, name: &str) -> Result<Address, Error> {
+		if let Ok(addr) = Address::from_str(name) {
+			return Ok(addr);
+		}
+
+		if let Some(addr) = self.resolved.lock().unwrap().get(name) {
+			return Ok(*addr);
+		}
+
+		// Names without an address resolve to zero, and names without a
+		// resolver fail to decode. Only a failure to reach the node is an
+		// error of its own
+		let addr = match self.ens.eth_address(name).await {
+			Ok(addr) if !addr.is_zero() => addr,
+			Err(QueryError::Api(e)) => return Err(e.into()),
+			_ => return Err(Error::UnresolvedName(name.to_owned())),
+		};
+
+		log::debug!("resolved {name} to {:?}", addr);
+
+		self.resolved.lock().unwrap().insert(name.to_owned(), addr);
+
+		Ok(addr)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::MockTransport;
+	use serde_json::{json, Value};
+
+	const NAME: &str = "vision.eth";
+
+	/// Encodes the address as the word returned by a contract call.
+	fn word(addr: Address) -> Value {
+		json!(format!("0x{:0>64}", hex::encode(addr)))
+	}
+
+	/// Gets a transport whose registry has a resolver for the name that
+	/// gives the address.
+	fn registry_resolving_to(addr: Address) -> MockTransport {
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_call", word(Address::repeat_byte(0x99)))
+			.respond("eth_call", json!(format!("0x{:0>64}", 1)))
+			.respond("eth_call", word(addr));
+
+		transport
+	}
+
+	#[actix_rt::test]
+	async fn resolves_hex_addresses_directly() {
+		let transport = MockTransport::default();
+		let resolver = AddressResolver::new(transport.clone());
+
+		assert_eq!(
+			resolver
+				.resolve("0x1111111111111111111111111111111111111111")
+				.await
+				.unwrap(),
+			Address::repeat_byte(0x11)
+		);
+		assert!(transport.calls("eth_call").is_empty());
+	}
+
+	#[actix_rt::test]
+	async fn resolves_names_once() {
+		let transport = registry_resolving_to(Address::repeat_byte(0x42));
+		let resolver = AddressResolver::new(transport.clone());
+
+		assert_eq!(
+			resolver.resolve(NAME).await.unwrap(),
+			Address::repeat_byte(0x42)
+		);
+		assert_eq!(transport.calls("eth_call").len(), 3);
+
+		// The second lookup is cached
+		assert_eq!(
+			resolver.resolve(NAME).await.unwrap(),
+			Address::repeat_byte(0x42)
+		);
+		assert_eq!(transport.calls("eth_call").len(), 3);
+	}
+
+	#[actix_rt::test]
+	async fn reports_names_without_addresses() {
+		let resolver = AddressResolver::new(registry_resolving_to(Address::zero()));
+
+		assert!(matches!(
+			resolver.resolve(NAME).await,
+			Err(Error::UnresolvedName(name)) if name == NAME
+		));
+	}
+
+	#[actix_rt::test]
+	async fn reports_names_without_resolvers() {
+		// The registry has no resolver, which has no code to answer
+		let transport = MockTransport::default();
+		transport
+			.respond("eth_call", word(Address::zero()))
+			.respond("eth_call", json!("0x"));
+		let resolver = AddressResolver::new(transport);
+
+		assert!(matches!(
+			resolver.resolve(NAME).await,
+			Err(Error::UnresolvedName(name)) if name == NAME
+		));
+	}
+
+	#[actix_rt::test]
+	async fn propagates_rpc_errors() {
+		// Nothing answers the registry lookup
+		let resolver = AddressResolver::new(MockTransport::default());
+
+		assert!(matches!(resolver.resolve(NAME).await, Err(Error::Web3(_))));
+	}
+}
@@ -3,8 +3,10 @@ use ipfs_api::Error as IpfsError;
 use serde_json::Error as SerializationError;
 use std::{error::Error as StdError, fmt, io::Error as IoError};
 use web3::{
-	contract::deploy::Error as DeployError, error::Error as Web3Error,
+	contract::{deploy::Error as DeployError, Error as QueryError},
+	error::Error as Web3Error,
 	ethabi::Error as ContractError,
+	types::{Address, H256, U256},
 };
 
 use super::super::cli::ParseError;
@@ -23,12 +25,87 @@ convertable_error! {
 		(Web3(Web3Error), [(Web3Error, Self::Web3)]),
 		(Contract(ContractError), [(ContractError, Self::Contract)]),
 		(Deploy(DeployError), [(DeployError, Self::Deploy)]),
+		(Query(QueryError), [(QueryError, Self::Query)]),
 
 		// File-related errors
 		(Io(Box<dyn StdError>), [(IoError, |e| Self::Io(Box::new(e)))]),
 		(Serialization(Box<dyn StdError>), [(SerializationError, |e| Self::Serialization(Box::new(e))), (FromHexError, |e| Self::Serialization(Box::new(e)))]),
 		(Ipfs(IpfsError), [(IpfsError, Self::Ipfs)]),
 		(InvalidInput),
+
+		// A module that failed validation, and the reason why
+		(InvalidModule(String, String)),
+
+		// The path of an icon that can't be uploaded, and the reason why
+		(InvalidIcon(String, String)),
+
+		// The path the contract artifact was expected at, and why it couldn't
+		// be opened
+		(MissingArtifact(String, IoError)),
+
+		// The paths of artifacts built by different toolchains, when the
+		// format wasn't specified
+		(AmbiguousArtifact(Vec<String>)),
+
+		// A field of the contract artifact, and what it should have been
+		(InvalidArtifact(&'static str, &'static str)),
+
+		// The path of a .bin file that couldn't be opened, and why
+		(MissingBytecodeFile(String, IoError)),
+
+		// The path of a .bin file that doesn't contain hex bytecode
+		(InvalidBytecode(String)),
+
+		// The path of an access list file, and why it couldn't be parsed
+		(InvalidAccessList(String, SerializationError)),
+
+		// Preflight failures: the expected and actual chain id's, and the
+		// deployer's balance and the cost of deploying
+		(ChainIdMismatch(u64, U256)),
+		(InsufficientFunds(U256, U256)),
+
+		// The metadata CID, and the most bytes the contract stores of it
+		(CidTooLong(String, usize)),
+
+		// The name and type of a constructor parameter that the argument
+		// specified for it doesn't fit
+		(InvalidConstructorArg(String, String)),
+
+		// The number of arguments the constructor takes, and how many were
+		// specified
+		(ConstructorArgCount(usize, usize)),
+
+		// An address flag's ENS name that doesn't resolve to an address
+		(UnresolvedName(String)),
+
+		// The deployer specified by flag, and the address of the private key
+		(DeployerMismatch(String, Address)),
+
+		// The transaction transferring the supply to the recipient, which
+		// reverted
+		(TransferFailed(H256)),
+
+		// The transaction of a deployment that was confirmed, but then
+		// orphaned by a reorg
+		(ReorgedOut(H256)),
+
+		// The deployer didn't approve the plan
+		(Declined),
+
+		// The chain the scan is on, and the chain its checkpoint was recorded
+		// on
+		(CheckpointMismatch(u64, u64)),
+
+		// The next block a list scan would have scanned when it ran out of
+		// time
+		(ScanTimedOut(u64)),
+
+		// CID's of uploaded modules that a second IPFS node couldn't fetch
+		(Unreplicated(Vec<String>)),
+
+		// The deployment didn't finish in time, and the hash of its
+		// transaction if it was sent
+		(Timeout(Option<H256>)),
 	}
 }
 
@@ -39,10 +116,90 @@ impl fmt::Display for Error {
 			Self::Web3(e) => write!(w, "web3 error: {e}"),
 			Self::Contract(e) => write!(w, "contract error: {e}"),
 			Self::Deploy(e) => write!(w, "deployment error: {e}"),
+			Self::Query(e) => write!(w, "contract query error: {e}"),
 			Self::Io(e) => write!(w, "IO error: {e}"),
 			Self::Serialization(e) => write!(w, "serialization error: {e}"),
 			Self::Ipfs(e) => write!(w, "ipfs network error: {e}"),
 			Self::InvalidInput => write!(w, "the inputted file could not be parsed properly"),
+			Self::InvalidModule(name, e) => write!(w, "module {name} is invalid: {e}"),
+			Self::InvalidIcon(path, e) => write!(w, "icon {path} can't be used: {e}"),
+			Self::MissingArtifact(path, e) => write!(
+				w,
+				"couldn't open the contract artifact at {path} ({e}); check that --contracts-dir and --artifact-path point to the compiled contract"
+			),
+			Self::MissingBytecodeFile(path, e) => write!(
+				w,
+				"couldn't open the bytecode file at {path} ({e}); check that --bytecode-file points to the compiled contract's .bin file"
+			),
+			Self::AmbiguousArtifact(paths) => write!(
+				w,
+				"found contract artifacts at {}; specify which to use with --artifact-format",
+				paths.join(", ")
+			),
+			Self::InvalidArtifact(field, expected) => write!(
+				w,
+				"the contract artifact's `{field}` field must be {expected}"
+			),
+			Self::InvalidBytecode(path) => write!(w, "{path} doesn't contain hex bytecode"),
+			Self::InvalidAccessList(path, e) => write!(
+				w,
+				"{path} isn't an access list of addresses and their 32-byte storage keys: {e}"
+			),
+			Self::ChainIdMismatch(expected, actual) => write!(
+				w,
+				"the node is on chain {actual}, but --eth-chain-id is {expected}"
+			),
+			Self::InsufficientFunds(balance, cost) => write!(
+				w,
+				"the deployer's balance of {balance} wei can't cover the deployment's cost of up to {cost} wei"
+			),
+			Self::CidTooLong(cid, size) => write!(
+				w,
+				"metadata CID {cid} is {} characters, but the contract only stores {size}; deploy a version of the contract that stores it as a string",
+				cid.len()
+			),
+			Self::InvalidConstructorArg(name, kind) => {
+				write!(w, "constructor argument `{name}` doesn't fit its type, {kind}")
+			}
+			Self::ConstructorArgCount(expected, actual) => write!(
+				w,
+				"the constructor takes {expected} arguments, but {actual} were specified"
+			),
+			Self::UnresolvedName(name) => write!(w, "{name} doesn't resolve to an address"),
+			Self::DeployerMismatch(deployer, actual) => write!(
+				w,
+				"the private key belongs to {:?}, but --deployer is {deployer}",
+				actual
+			),
+			Self::TransferFailed(hash) => write!(
+				w,
+				"the DAO was deployed, but transaction {:?} transferring the supply to the recipient failed",
+				hash
+			),
+			Self::ReorgedOut(hash) => write!(
+				w,
+				"the deployment in transaction {:?} was confirmed, but then reorged out of the chain",
+				hash
+			),
+			Self::Declined => write!(w, "the deployment plan was declined"),
+			Self::CheckpointMismatch(expected, actual) => write!(
+				w,
+				"the checkpoint was recorded on chain {actual}, but --eth-chain-id is {expected}"
+			),
+			Self::Unreplicated(cids) => write!(
+				w,
+				"the second IPFS node couldn't fetch {}",
+				cids.join(", ")
+			),
+			Self::ScanTimedOut(next) => {
+				write!(w, "the scan timed out before reaching block {next}")
+			}
+			Self::Timeout(Some(hash)) => write!(
+				w,
+				"the deployment timed out with transaction {:?} pending",
+				hash
+			),
+			Self::Timeout(None) => write!(w, "the deployment timed out before it was sent"),
 		}
 	}
 }
@@ -55,9 +212,32 @@ impl StdError for Error {
 			Self::Io(e) => Some(e.as_ref()),
 			Self::Contract(e) => Some(e),
 			Self::Deploy(e) => Some(e),
+			Self::Query(e) => Some(e),
 			Self::Serialization(e) => Some(e.as_ref()),
 			Self::Ipfs(e) => Some(e),
-			Self::InvalidInput => None,
+			Self::MissingArtifact(_, e) => Some(e),
+			Self::MissingBytecodeFile(_, e) => Some(e),
+			Self::InvalidAccessList(_, e) => Some(e),
+			Self::InvalidInput
+			| Self::InvalidModule(..)
+			| Self::InvalidIcon(..)
+			| Self::AmbiguousArtifact(_)
+			| Self::InvalidArtifact(..)
+			| Self::InvalidBytecode(_)
+			| Self::ChainIdMismatch(..)
+			| Self::InsufficientFunds(..)
+			| Self::CidTooLong(..)
+			| Self::InvalidConstructorArg(..)
+			| Self::ConstructorArgCount(..)
+			| Self::UnresolvedName(_)
+			| Self::DeployerMismatch(..)
+			| Self::TransferFailed(_)
+			| Self::ReorgedOut(_)
+			| Self::Declined
+			| Self::CheckpointMismatch(..)
+			| Self::ScanTimedOut(_)
+			| Self::Unreplicated(_)
+			| Self::Timeout(_) => None,
 		}
 	}
 }
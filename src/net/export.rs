@@ -0,0 +1,117 @@
+use std::{
+	fs::OpenOptions,
+	io::{BufWriter, Write},
+};
+
+use super::{contract::Deployment, error::Error};
+
+/// The columns of an exported registry of deployments.
+const CSV_HEADER: &str = "chain_id,address,tx_hash,metadata_cid,timestamp";
+
+/// Writes the deployments as CSV rows to the file at the path, either
+/// appending to it or replacing it. A header is written if the file ends up
+/// with no rows before these.
+pub fn write_csv(
+	path: &str,
+	chain_id: u64,
+	deployments: &[Deployment],
+	append: bool,
+) -> Result<(), Error> {
+	let f = OpenOptions::new()
+		.create(true)
+		.write(true)
+		.append(append)
+		.truncate(!append)
+		.open(path)?;
+	let empty = f.metadata()?.len() == 0;
+	let mut w = BufWriter::new(f);
+
+	if empty {
+		writeln!(w, "{CSV_HEADER}")?;
+	}
+
+	for Deployment {
+		address,
+		tx_hash,
+		metadata_cid,
+		timestamp,
+		..
+	} in deployments
+	{
+		writeln!(
+			w,
+			"{chain_id},{:?},{:?},{},{timestamp}",
+			address,
+			tx_hash,
+			escape(metadata_cid.as_deref().unwrap_or_default())
+		)?;
+	}
+
+	w.flush()?;
+
+	Ok(())
+}
+
+/// Quotes the field if it contains characters that are special in CSV.
+fn escape(field: &str) -> String {
+	if field.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_owned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::temp_dir;
+	use std::fs;
+	use web3::types::{Address, H256};
+
+	fn deployment(metadata_cid: Option<&str>) -> Deployment {
+		Deployment {
+			address: Address::repeat_byte(0x11),
+			block_number: 7,
+			tx_hash: H256::repeat_byte(0x22),
+			timestamp: 1_700_000_000,
+			metadata_cid: metadata_cid.map(str::to_owned),
+			gas: None,
+			recorded: None,
+		}
+	}
+
+	#[test]
+	fn escapes_special_fields() {
+		assert_eq!(escape("bafy"), "bafy");
+		assert_eq!(escape(""), "");
+		assert_eq!(escape("a,b"), "\"a,b\"");
+		assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+		assert_eq!(escape("a\nb"), "\"a\nb\"");
+		assert_eq!(escape("a\rb"), "\"a\rb\"");
+	}
+
+	#[test]
+	fn writes_rows_under_one_header() {
+		let dir = temp_dir("export");
+		let path = dir.join("deployments.csv").display().to_string();
+
+		write_csv(&path, 1, &[deployment(Some("bafy"))], false).unwrap();
+		write_csv(&path, 1, &[deployment(None)], true).unwrap();
+		let csv = fs::read_to_string(&path).unwrap();
+
+		// Replacing the file starts it over
+		write_csv(&path, 5, &[], false).unwrap();
+		let replaced = fs::read_to_string(&path).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+
+		let address = format!("{:?}", Address::repeat_byte(0x11));
+		let tx_hash = format!("{:?}", H256::repeat_byte(0x22));
+		assert_eq!(
+			csv,
+			format!(
+				"{CSV_HEADER}\n1,{address},{tx_hash},bafy,1700000000\n1,{address},{tx_hash},,1700000000\n"
+			)
+		);
+		assert_eq!(replaced, format!("{CSV_HEADER}\n"));
+	}
+}
@@ -1,3 +1,8 @@
+pub mod cache;
+pub mod cid;
 pub mod contract;
+pub mod doctor;
+pub mod ens;
 pub mod error;
+pub mod export;
 pub mod payload;
@@ -1,75 +1,311 @@
-use futures::future::{self, TryFutureExt};
-use ipfs_api::{response::Cid, IpfsApi, IpfsClient};
-use serde::Serialize;
+use actix_rt::time;
+use flate2::{write::GzEncoder, Compression};
+use futures::{
+	future::{self, TryFutureExt},
+	TryStreamExt,
+};
+use ipfs_api::{request::ApiRequest, response::Cid, IpfsApi, IpfsClient};
+use ipfs_api_prelude::Backend;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-	collections::HashMap,
-	fs::File,
-	io::{Cursor, Read},
+	collections::BTreeMap,
+	fs,
+	io::{self, Cursor, Read, Write},
+	time::Duration,
+};
+
+use super::{
+	super::{
+		cli::Module,
+		loader,
+		wasm::{self, ModuleOptions},
+	},
+	cache::UploadCache,
+	cid,
+	error::Error,
 };
 
-use super::error::Error;
+/// The version of the metadata format that DAO's are deployed with, unless
+/// overridden. Metadata from before it was recorded is version 1.
+const SCHEMA_VERSION: u32 = 1;
+
+/// How full the IPFS node's repo may get, as a percent of its storage limit,
+/// before it is warned about.
+const STORAGE_WARN_PERCENT: u64 = 90;
+
+/// The largest image that may be uploaded as a DAO's icon.
+const MAX_ICON_BYTES: usize = 1024 * 1024;
+
+/// How long a second node is given to fetch each uploaded file before it's
+/// considered unreachable.
+const REPLICATION_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Represents an entry in an Idea's metadata specifying an executable target
 /// of a DAO.
 #[derive(Serialize, Debug)]
 struct IdeaPayload {
 	/// JS that loads the module (only for kernel modules) represented as a UnixFs file
-	loader: Vec<HashMap<&'static str, String>>,
+	loader: Vec<BTreeMap<&'static str, String>>,
 
 	/// WASM payload of the module itself represented as a UnixFs file
-	module: Vec<HashMap<&'static str, String>>,
+	module: Vec<BTreeMap<&'static str, String>>,
 }
 
 /// Represents metadata attached to a DAO.
 #[derive(Serialize, Debug)]
 struct IdeaMetadata<'a> {
+	/// Version of the metadata format, which consumers can branch on
+	schema_version: u32,
+
 	/// Name of the DAO
 	title: &'a str,
 
 	/// Markdown description of the DAO
-	description: &'a str,
+	description: Description<'a>,
 
-	/// References to the installed modules
+	/// How the linked description is compressed, if it isn't inline
+	#[serde(skip_serializing_if = "Option::is_none")]
+	description_encoding: Option<&'static str>,
+
+	/// References to the installed modules, in the order they were specified.
 	/// CID's are represented in the IPLD dag-json format as maps with one
 	/// entry "/" whose value is the string-encoded CID
-	payload: Vec<HashMap<&'static str, String>>,
+	payload: Vec<BTreeMap<&'static str, String>>,
+
+	/// Image representing the DAO represented as a UnixFs file
+	#[serde(skip_serializing_if = "Option::is_none")]
+	icon: Option<BTreeMap<&'static str, String>>,
+}
+
+/// Details of a DAO recorded in its metadata, alongside its modules.
+pub struct MetadataDetails<'a> {
+	pub(crate) title: &'a str,
+	pub(crate) description: &'a str,
+
+	// Whether the description is uploaded separately, gzipped
+	pub(crate) compress_description: bool,
+
+	// The path of an image to upload as the DAO's icon
+	pub(crate) icon: Option<&'a str>,
+
+	// The version of the metadata format recorded, if not the current one
+	pub(crate) schema_version: Option<u32>,
+}
+
+/// The description of a DAO, as stored in its metadata.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Description<'a> {
+	Inline(&'a str),
+
+	// A link to a UnixFS file containing the compressed description
+	Compressed(BTreeMap<&'static str, String>),
+}
+
+/// A link to another IPLD node, in the dag-json format.
+#[derive(Deserialize)]
+struct Link {
+	#[serde(rename = "/")]
+	cid: String,
+}
+
+/// An entry in a DAO's metadata, as read back from IPFS.
+#[derive(Deserialize)]
+struct StoredPayload {
+	loader: Vec<Link>,
+	module: Vec<Link>,
+
+	// Not written by daowiz, but reported if other tools include them
+	name: Option<String>,
+	version: Option<String>,
+}
+
+/// A DAO's metadata, as read back from IPFS.
+#[derive(Deserialize)]
+struct StoredMetadata {
+	payload: Vec<Link>,
+}
+
+/// A module installed in a DAO, as listed in its metadata.
+#[derive(Serialize, Debug)]
+pub struct InstalledModule {
+	pub(crate) loader: String,
+	pub(crate) module: String,
+
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub(crate) version: Option<String>,
+}
+
+/// Adds the file to IPFS, returning its CID. Skips the upload if the cache
+/// already holds a CID for identical content, or if only the CID is needed.
+async fn add_file(
+	ipfs: &IpfsClient,
+	cache: Option<&UploadCache>,
+	content: Vec<u8>,
+	hash_only: bool,
+) -> Result<String, Error> {
+	if hash_only {
+		return Ok(cid::file_cid(&content));
+	}
+
+	let cached = match cache {
+		Some(cache) => cache.get(ipfs, &content).await,
+		None => None,
+	};
+
+	if let Some(cid) = cached {
+		return Ok(cid);
+	}
+
+	let cid = ipfs
+		.add(Cursor::new(content.clone()))
+		.await
+		.map_err(Error::Ipfs)?
+		.hash;
+
+	if let Some(cache) = cache {
+		cache.insert(&content, cid.clone());
+	}
+
+	Ok(cid)
+}
+
+/// Puts the node on IPFS in the dag-json format, returning its CID. Only
+/// computes the CID if the node shouldn't be stored.
+async fn put_node<T: Serialize>(
+	ipfs: &IpfsClient,
+	node: &T,
+	hash_only: bool,
+) -> Result<Cid, Error> {
+	if hash_only {
+		return Ok(Cid {
+			cid_string: cid::dag_node_cid(&serde_json::to_value(node)?)?,
+		});
+	}
+
+	ipfs.dag_put(Cursor::new(serde_json::to_string(node)?))
+		.await
+		.map(|resp| resp.cid)
+		.map_err(Error::Ipfs)
+}
+
+/// Detects the format of an icon from its first bytes, which must be a PNG,
+/// JPEG, GIF, WebP, or SVG image.
+fn icon_format(src: &[u8]) -> Option<&'static str> {
+	if src.starts_with(b"\x89PNG\r\n\x1a\n") {
+		Some("png")
+	} else if src.starts_with(b"\xff\xd8\xff") {
+		Some("jpeg")
+	} else if src.starts_with(b"GIF87a") || src.starts_with(b"GIF89a") {
+		Some("gif")
+	} else if src.starts_with(b"RIFF") && src.get(8..12) == Some(b"WEBP") {
+		Some("webp")
+	} else if String::from_utf8_lossy(&src[..src.len().min(1024)]).contains("<svg") {
+		Some("svg")
+	} else {
+		None
+	}
+}
+
+/// Reads the icon at the path, checking that it is an image small enough to
+/// be fetched alongside the metadata.
+fn read_icon(path: &str) -> Result<Vec<u8>, Error> {
+	let src = fs::read(path)?;
+
+	if src.len() > MAX_ICON_BYTES {
+		return Err(Error::InvalidIcon(
+			path.to_owned(),
+			format!(
+				"it is {} bytes, but at most {MAX_ICON_BYTES} are allowed",
+				src.len()
+			),
+		));
+	}
+
+	match icon_format(&src) {
+		Some(format) => {
+			log::debug!("uploading {format} icon {path}");
+
+			Ok(src)
+		}
+		None => Err(Error::InvalidIcon(
+			path.to_owned(),
+			"it isn't a PNG, JPEG, GIF, WebP, or SVG image".to_owned(),
+		)),
+	}
 }
 
 /// Creates a metadata instance using the provided details, returning the CID
-/// of the uploaded DAG node.
+/// of the uploaded DAG node. Nothing is stored on IPFS if only the CID's are
+/// needed.
 pub async fn deploy_metadata(
 	ipfs: &IpfsClient,
-	title: &str,
-	description: &str,
-	modules: Vec<(File, File)>,
+	cache: Option<&UploadCache>,
+	MetadataDetails {
+		title,
+		description,
+		compress_description,
+		icon,
+		schema_version,
+	}: MetadataDetails<'_>,
+	modules: Vec<Module>,
+	options: &ModuleOptions,
+	hash_only: bool,
+	print_metadata: bool,
 ) -> Result<Cid, Error> {
-	// Load the JS and WASM specified by each module, and get the CID once
-	// they are published to IPFS
-	let entries: Vec<Cid> = future::try_join_all(modules.into_iter().enumerate().map(
-		async move |(i, (mut load, mut module))| {
-			// Modules have a WASM and JS payload. Load the WASM
-			let mut src = Vec::new();
-			module.read_to_end(&mut src)?;
-
-			// And load the JavaScript
-			let mut loader = Vec::new();
-			load.read_to_end(&mut loader)?;
-
-			let loader_cid = ipfs
-				.add(Cursor::new(loader))
-				.await
-				.map_err(Error::Ipfs)?
-				.hash;
-			let module_cid = ipfs.add(Cursor::new(src)).await.map_err(Error::Ipfs)?.hash;
+	// Load the JS and WASM specified by each module, catching broken modules
+	// before anything is uploaded
+	let prepared = modules
+		.into_iter()
+		.map(
+			|Module {
+			     name,
+			     loader: mut load,
+			     mut module,
+			 }| {
+				// Modules have a WASM and JS payload. Load the WASM
+				let mut src = Vec::new();
+				module.read_to_end(&mut src)?;
+				let src = wasm::prepare(&name, src, options)?;
+
+				// And load the JavaScript
+				let mut loader = Vec::new();
+				load.read_to_end(&mut loader)?;
+				loader::check(&name, &loader, options)?;
+
+				Ok::<_, Error>((name, loader, src))
+			},
+		)
+		.collect::<Result<Vec<_>, _>>()?;
+	let icon = icon.map(read_icon).transpose()?;
+
+	// Get the CID of each module once it is published to IPFS
+	let entries: Vec<Cid> = future::try_join_all(prepared.into_iter().enumerate().map(
+		|(i, (name, loader, src))| async move {
+			let loader_cid = add_file(ipfs, cache, loader, hash_only).await?;
+			let module_cid = add_file(ipfs, cache, src, hash_only).await?;
+
+			// Identical files can't be a loader and the WASM it loads
+			if let Some(e) = check_slots(&loader_cid, &module_cid) {
+				let e = Error::InvalidModule(name.clone(), e);
+
+				if options.strict {
+					return Err(e);
+				}
+
+				eprintln!("warning: {e}");
+			}
 
 			let loader_cid_rep = {
-				let mut m = HashMap::new();
+				let mut m = BTreeMap::new();
 				m.insert("/", loader_cid);
 
 				m
 			};
 			let mod_cid_rep = {
-				let mut m = HashMap::new();
+				let mut m = BTreeMap::new();
 				m.insert("/", module_cid);
 
 				m
@@ -81,13 +317,12 @@ pub async fn deploy_metadata(
 			};
 
 			// Upload the metadata to IPFS
-			ipfs.dag_put(Cursor::new(serde_json::to_string(&module)?))
-				.map_ok(|resp| {
+			put_node(ipfs, &module, hash_only)
+				.map_ok(|cid| {
 					log::debug!("finished deploying module {}", i);
 
-					resp.cid
+					cid
 				})
-				.map_err(Error::Ipfs)
 				.await
 		},
 	))
@@ -95,24 +330,428 @@ pub async fn deploy_metadata(
 
 	// See above explanation: DAG-JSON IPLD format requires that CID's are
 	// represented as { "/": CID } maps (weird yea ik)
-	let payload: Vec<HashMap<&'static str, String>> = entries
+	let payload: Vec<BTreeMap<&'static str, String>> = entries
 		.into_iter()
 		.map(|cid| {
-			let mut m = HashMap::new();
+			let mut m = BTreeMap::new();
 			m.insert("/", cid.cid_string);
 
 			m
 		})
 		.collect();
 
+	// Long descriptions would bloat the metadata node, so they can be
+	// uploaded separately and linked to instead
+	let (description, description_encoding) = if compress_description {
+		let cid = add_file(ipfs, cache, gzip(description.as_bytes())?, hash_only).await?;
+		let mut m = BTreeMap::new();
+		m.insert("/", cid);
+
+		(Description::Compressed(m), Some("gzip"))
+	} else {
+		(Description::Inline(description), None)
+	};
+
+	let icon = match icon {
+		Some(src) => {
+			let cid = add_file(ipfs, cache, src, hash_only).await?;
+			let mut m = BTreeMap::new();
+			m.insert("/", cid);
+
+			Some(m)
+		}
+		None => None,
+	};
+
 	let meta = IdeaMetadata {
+		schema_version: schema_version.unwrap_or(SCHEMA_VERSION),
 		title,
 		description,
+		description_encoding,
 		payload,
+		icon,
 	};
 
-	ipfs.dag_put(Cursor::new(serde_json::to_string(&meta)?))
-		.await
-		.map(|resp| resp.cid)
-		.map_err(|e| e.into())
+	// Printed to stderr, keeping stdout for the command's own output
+	if print_metadata {
+		print_metadata_to(&mut io::stderr(), &meta)?;
+	}
+
+	put_node(ipfs, &meta, hash_only).await
+}
+
+/// Writes the metadata as pretty JSON for review, on a line of its own.
+fn print_metadata_to<T: Serialize>(w: &mut impl Write, meta: &T) -> Result<(), Error> {
+	serde_json::to_writer_pretty(&mut *w, meta)?;
+	writeln!(w)?;
+
+	Ok(())
+}
+
+/// Compresses the content as tightly as gzip can.
+fn gzip(content: &[u8]) -> Result<Vec<u8>, Error> {
+	let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+	encoder.write_all(content)?;
+
+	Ok(encoder.finish()?)
+}
+
+/// Describes what's wrong with the CID's that a module's files were uploaded
+/// as, if they can't be a loader and the WASM it loads.
+fn check_slots(loader: &str, module: &str) -> Option<String> {
+	if loader.is_empty() || module.is_empty() {
+		Some("its loader or WASM has no CID".to_owned())
+	} else if loader == module {
+		Some(format!("its loader and WASM are the same file ({loader})"))
+	} else {
+		None
+	}
+}
+
+/// Asks for the size of the node's repo, without counting its objects.
+#[derive(Serialize)]
+struct RepoStat {
+	#[serde(rename = "size-only")]
+	size_only: bool,
+}
+
+impl ApiRequest for RepoStat {
+	const PATH: &'static str = "/repo/stat";
+}
+
+/// How much the node's repo stores, and the most it may store.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RepoStatResponse {
+	repo_size: u64,
+	storage_max: u64,
+}
+
+/// Warns if the node's repo is nearly out of room once the bytes are added
+/// to it, or if it can't be asked.
+pub async fn check_storage(ipfs: &IpfsClient, incoming: u64) {
+	if let Some(warning) = storage_warning(ipfs, incoming).await {
+		eprintln!("warning: {warning}");
+	}
+}
+
+/// Describes why the node's repo may not have room for the bytes, if it may
+/// not.
+async fn storage_warning(ipfs: &IpfsClient, incoming: u64) -> Option<String> {
+	let stat: RepoStatResponse = match ipfs.request(RepoStat { size_only: true }, None).await {
+		Ok(stat) => stat,
+		Err(e) => return Some(format!("couldn't read the IPFS node's storage: {e}")),
+	};
+	let size = stat.repo_size + incoming;
+
+	log::debug!(
+		"IPFS repo will use {size} of {} bytes once uploaded",
+		stat.storage_max
+	);
+
+	(size >= stat.storage_max / 100 * STORAGE_WARN_PERCENT).then(|| {
+		format!(
+			"the IPFS node's repo will use {size} of its {} bytes of storage once the modules are uploaded",
+			stat.storage_max
+		)
+	})
+}
+
+/// Fetches the DAG node with the CID, in the dag-json format.
+async fn get_node<T: DeserializeOwned>(ipfs: &IpfsClient, cid: &str) -> Result<T, Error> {
+	let src = ipfs
+		.dag_get(cid)
+		.map_ok(|chunk| chunk.to_vec())
+		.try_concat()
+		.await?;
+
+	Ok(serde_json::from_slice(&src)?)
+}
+
+/// Lists the modules installed by the metadata with the CID, in the order
+/// they were specified.
+pub async fn read_modules(ipfs: &IpfsClient, cid: &str) -> Result<Vec<InstalledModule>, Error> {
+	let meta: StoredMetadata = get_node(ipfs, cid).await?;
+
+	future::try_join_all(meta.payload.into_iter().map(|Link { cid }| async move {
+		let StoredPayload {
+			loader,
+			module,
+			name,
+			version,
+		} = get_node(ipfs, &cid).await?;
+
+		// Each file is a single link
+		match (loader.into_iter().next(), module.into_iter().next()) {
+			(Some(Link { cid: loader }), Some(Link { cid: module })) => Ok(InstalledModule {
+				loader,
+				module,
+				name,
+				version,
+			}),
+			_ => Err(Error::InvalidInput),
+		}
+	}))
+	.await
+}
+
+/// Asks the second node to fetch the loader and module of each module
+/// installed by the metadata with the CID, returning the CID's it couldn't
+/// fetch in time.
+pub async fn check_replication(
+	ipfs: &IpfsClient,
+	replica: &IpfsClient,
+	cid: &str,
+) -> Result<Vec<String>, Error> {
+	let cids = read_modules(ipfs, cid)
+		.await?
+		.into_iter()
+		.flat_map(|InstalledModule { loader, module, .. }| [loader, module]);
+
+	let reachable = future::join_all(cids.map(|cid| async move {
+		let fetch = replica
+			.cat(&cid)
+			.map_ok(|chunk| chunk.len())
+			.try_collect::<Vec<_>>();
+
+		match time::timeout(REPLICATION_TIMEOUT, fetch).await {
+			Ok(Ok(_)) => {
+				log::debug!("{cid} is reachable from the second node");
+
+				None
+			}
+			Ok(Err(e)) => {
+				log::debug!("second node failed to fetch {cid}: {e}");
+
+				Some(cid)
+			}
+			Err(_) => Some(cid),
+		}
+	}))
+	.await;
+
+	Ok(reachable.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_support::{fake_node, fake_server, missing_node};
+	use flate2::read::GzDecoder;
+	use ipfs_api::TryFromUri;
+	use serde_json::{json, Value};
+
+	fn link(cid: &str) -> BTreeMap<&'static str, String> {
+		BTreeMap::from([("/", cid.to_owned())])
+	}
+
+	#[test]
+	fn serializes_metadata_in_field_order() {
+		// The metadata's CID depends on its exact bytes
+		let meta = IdeaMetadata {
+			schema_version: SCHEMA_VERSION,
+			title: "Vision DAO",
+			description: Description::Inline("A *DAO*"),
+			description_encoding: None,
+			payload: vec![link("bafymodule")],
+			icon: None,
+		};
+
+		assert_eq!(
+			serde_json::to_string(&meta).unwrap(),
+			r#"{"schema_version":1,"title":"Vision DAO","description":"A *DAO*","payload":[{"/":"bafymodule"}]}"#
+		);
+	}
+
+	#[test]
+	fn serializes_linked_metadata() {
+		let meta = IdeaMetadata {
+			schema_version: 2,
+			title: "Vision DAO",
+			description: Description::Compressed(link("QmDescription")),
+			description_encoding: Some("gzip"),
+			payload: vec![link("bafya"), link("bafyb")],
+			icon: Some(link("QmIcon")),
+		};
+
+		assert_eq!(
+			serde_json::to_string(&meta).unwrap(),
+			r#"{"schema_version":2,"title":"Vision DAO","description":{"/":"QmDescription"},"description_encoding":"gzip","payload":[{"/":"bafya"},{"/":"bafyb"}],"icon":{"/":"QmIcon"}}"#
+		);
+	}
+
+	#[test]
+	fn serializes_modules() {
+		let module = IdeaPayload {
+			loader: vec![link("QmLoader")],
+			module: vec![link("QmModule")],
+		};
+
+		assert_eq!(
+			serde_json::to_string(&module).unwrap(),
+			r#"{"loader":[{"/":"QmLoader"}],"module":[{"/":"QmModule"}]}"#
+		);
+	}
+
+	#[test]
+	fn detects_icon_formats() {
+		assert_eq!(icon_format(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+		assert_eq!(icon_format(b"\xff\xd8\xff\xe0"), Some("jpeg"));
+		assert_eq!(icon_format(b"GIF89a"), Some("gif"));
+		assert_eq!(icon_format(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+		assert_eq!(icon_format(b"<?xml?><svg></svg>"), Some("svg"));
+		assert_eq!(icon_format(b"plain text"), None);
+	}
+
+	#[test]
+	fn checks_module_slots() {
+		assert_eq!(check_slots("QmLoader", "QmModule"), None);
+		assert!(check_slots("QmSame", "QmSame").is_some_and(|e| e.contains("QmSame")));
+		assert!(check_slots("", "QmModule").is_some());
+		assert!(check_slots("QmLoader", "").is_some());
+	}
+
+	#[test]
+	fn gzips_descriptions() {
+		let description = "# Vision DAO\n\n".repeat(100);
+		let compressed = gzip(description.as_bytes()).unwrap();
+		assert!(compressed.len() < description.len());
+
+		let mut decompressed = String::new();
+		GzDecoder::new(compressed.as_slice())
+			.read_to_string(&mut decompressed)
+			.unwrap();
+		assert_eq!(decompressed, description);
+	}
+
+	#[test]
+	fn prints_metadata_as_pretty_json() {
+		let meta = IdeaMetadata {
+			schema_version: SCHEMA_VERSION,
+			title: "Vision DAO",
+			description: Description::Inline("A *DAO*"),
+			description_encoding: None,
+			payload: vec![link("bafymodule")],
+			icon: None,
+		};
+		let mut printed = Vec::new();
+		print_metadata_to(&mut printed, &meta).unwrap();
+
+		assert_eq!(
+			String::from_utf8(printed).unwrap(),
+			format!("{}\n", serde_json::to_string_pretty(&meta).unwrap())
+		);
+	}
+
+	/// Gets a client for a fake IPFS node holding the DAG nodes, by CID.
+	fn node_with_dag(nodes: Vec<(&'static str, Value)>) -> IpfsClient {
+		let uri = fake_server(move |req| {
+			match nodes
+				.iter()
+				.find(|(cid, _)| req.contains(&format!("arg={cid}")))
+			{
+				Some((_, node)) => (200, node.to_string()),
+				None => (
+					500,
+					r#"{"Message":"not found","Code":0,"Type":"error"}"#.to_owned(),
+				),
+			}
+		});
+
+		IpfsClient::from_str(&uri).unwrap()
+	}
+
+	#[actix_rt::test]
+	async fn reads_installed_modules() {
+		let ipfs = node_with_dag(vec![
+			(
+				"QmMetadata",
+				json!({ "title": "Vision DAO", "payload": [{ "/": "QmFirst" }, { "/": "QmSecond" }] }),
+			),
+			(
+				"QmFirst",
+				json!({ "loader": [{ "/": "QmLoaderA" }], "module": [{ "/": "QmModuleA" }] }),
+			),
+			(
+				"QmSecond",
+				json!({
+					"loader": [{ "/": "QmLoaderB" }],
+					"module": [{ "/": "QmModuleB" }],
+					"name": "voting",
+					"version": "1.0.0",
+				}),
+			),
+		]);
+
+		let modules = read_modules(&ipfs, "QmMetadata").await.unwrap();
+		assert_eq!(
+			serde_json::to_value(&modules).unwrap(),
+			json!([
+				{ "loader": "QmLoaderA", "module": "QmModuleA" },
+				{ "loader": "QmLoaderB", "module": "QmModuleB", "name": "voting", "version": "1.0.0" },
+			])
+		);
+	}
+
+	#[actix_rt::test]
+	async fn rejects_modules_missing_files() {
+		let ipfs = node_with_dag(vec![
+			("QmMetadata", json!({ "payload": [{ "/": "QmFirst" }] })),
+			(
+				"QmFirst",
+				json!({ "loader": [], "module": [{ "/": "QmModuleA" }] }),
+			),
+		]);
+
+		assert!(matches!(
+			read_modules(&ipfs, "QmMetadata").await,
+			Err(Error::InvalidInput)
+		));
+	}
+
+	#[actix_rt::test]
+	async fn reports_unreplicated_files() {
+		let ipfs = node_with_dag(vec![
+			("QmMetadata", json!({ "payload": [{ "/": "QmFirst" }] })),
+			(
+				"QmFirst",
+				json!({ "loader": [{ "/": "QmLoader" }], "module": [{ "/": "QmModule" }] }),
+			),
+		]);
+
+		// The second node can only fetch the loader
+		let replica = IpfsClient::from_str(&fake_server(|req| {
+			if req.contains("arg=QmLoader") {
+				(200, "export default {}".to_owned())
+			} else {
+				(
+					500,
+					r#"{"Message":"not found","Code":0,"Type":"error"}"#.to_owned(),
+				)
+			}
+		}))
+		.unwrap();
+
+		assert_eq!(
+			check_replication(&ipfs, &replica, "QmMetadata")
+				.await
+				.unwrap(),
+			["QmModule"]
+		);
+	}
+
+	#[actix_rt::test]
+	async fn warns_of_nearly_full_repos() {
+		let ipfs = fake_node(200, r#"{"RepoSize":800,"StorageMax":1000}"#);
+
+		assert!(storage_warning(&ipfs, 99).await.is_none());
+		assert_eq!(
+			storage_warning(&ipfs, 100).await.unwrap(),
+			"the IPFS node's repo will use 900 of its 1000 bytes of storage once the modules are uploaded"
+		);
+		assert!(storage_warning(&missing_node(), 0)
+			.await
+			.unwrap()
+			.starts_with("couldn't read the IPFS node's storage"));
+	}
 }
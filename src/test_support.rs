@@ -0,0 +1,193 @@
+use futures::future::{self, Ready};
+use ipfs_api::{IpfsClient, TryFromUri};
+use jsonrpc_core::{Call, MethodCall, Params};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::Value;
+use std::{
+	cell::RefCell,
+	collections::{HashMap, VecDeque},
+	env, fs,
+	io::{Read, Write},
+	net::{TcpListener, TcpStream},
+	path::PathBuf,
+	process,
+	sync::{Arc, Mutex},
+	thread,
+};
+use web3::{error::TransportError, helpers, RequestId, Transport};
+
+/// Creates an empty directory for the test to work in, unique to the test
+/// and to this run.
+pub fn temp_dir(name: &str) -> PathBuf {
+	let dir = env::temp_dir().join(format!("daowiz-{}-{name}", process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+
+	dir
+}
+
+/// Starts a fake HTTP server that answers each request with the status and
+/// JSON body picked for its request line, and gets its URL.
+pub fn fake_server(respond: impl Fn(&str) -> (u16, String) + Send + 'static) -> String {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	thread::spawn(move || {
+		for stream in listener.incoming() {
+			let mut stream = stream.unwrap();
+			let req = read_request(&mut stream);
+
+			let (status, body) = respond(req.lines().next().unwrap_or_default());
+			let _ = write!(
+				stream,
+				"HTTP/1.1 {status} OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+				body.len()
+			);
+		}
+	});
+
+	format!("http://{addr}")
+}
+
+/// Reads the whole of the HTTP request, so that the connection isn't reset
+/// by closing it with some of the request still unread.
+fn read_request(stream: &mut TcpStream) -> String {
+	let mut req = Vec::new();
+	let mut buf = [0; 4096];
+
+	loop {
+		let n = stream.read(&mut buf).unwrap_or(0);
+		req.extend_from_slice(&buf[..n]);
+
+		let text = String::from_utf8_lossy(&req);
+		let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+			let len = head
+				.lines()
+				.find_map(|line| {
+					let (name, value) = line.split_once(':')?;
+					name.eq_ignore_ascii_case("content-length")
+						.then(|| value.trim().parse().ok())?
+				})
+				.unwrap_or(0);
+
+			body.len() >= len
+		});
+
+		if n == 0 || complete {
+			return text.into_owned();
+		}
+	}
+}
+
+/// Gets a client for a fake IPFS node that answers every API call with the
+/// status and body.
+pub fn fake_node(status: u16, body: &'static str) -> IpfsClient {
+	IpfsClient::from_str(&fake_server(move |_| (status, body.to_owned()))).unwrap()
+}
+
+/// Gets a client for an IPFS node that can't be reached.
+pub fn missing_node() -> IpfsClient {
+	IpfsClient::from_str("http://127.0.0.1:1").unwrap()
+}
+
+/// A web3 transport that answers each RPC method with canned results, and
+/// records the calls made.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+	// The results still to give for each method. The last one is repeated
+	results: Arc<Mutex<HashMap<String, VecDeque<Value>>>>,
+	calls: Arc<Mutex<Vec<MethodCall>>>,
+}
+
+impl MockTransport {
+	/// Queues up the result for the next call of the method.
+	pub fn respond(&self, method: &str, result: Value) -> &Self {
+		self.results
+			.lock()
+			.unwrap()
+			.entry(method.to_owned())
+			.or_default()
+			.push_back(result);
+
+		self
+	}
+
+	/// Gets the parameters of each call of the method so far.
+	pub fn calls(&self, method: &str) -> Vec<Vec<Value>> {
+		self.calls
+			.lock()
+			.unwrap()
+			.iter()
+			.filter(|call| call.method == method)
+			.map(|call| match &call.params {
+				Params::Array(params) => params.clone(),
+				_ => Vec::new(),
+			})
+			.collect()
+	}
+}
+
+impl Transport for MockTransport {
+	type Out = Ready<web3::Result<Value>>;
+
+	fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+		let mut calls = self.calls.lock().unwrap();
+		let id = calls.len();
+		let request = helpers::build_request(id, method, params);
+		if let Call::MethodCall(call) = &request {
+			calls.push(call.clone());
+		}
+
+		(id, request)
+	}
+
+	fn send(&self, _: RequestId, request: Call) -> Self::Out {
+		let Call::MethodCall(call) = request else {
+			unreachable!("only method calls are sent");
+		};
+		let mut results = self.results.lock().unwrap();
+		let result = match results.get_mut(&call.method) {
+			Some(queued) if queued.len() > 1 => queued.pop_front(),
+			Some(queued) => queued.front().cloned(),
+			None => None,
+		};
+
+		future::ready(result.ok_or_else(|| {
+			web3::Error::Transport(TransportError::Message(format!(
+				"unexpected call of {}",
+				call.method
+			)))
+		}))
+	}
+}
+
+thread_local! {
+	static LOGGED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A logger that records each message for the thread that logged it, so that
+/// tests running side by side only see their own.
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+	fn enabled(&self, _: &Metadata) -> bool {
+		true
+	}
+
+	fn log(&self, record: &Record) {
+		LOGGED.with(|logged| logged.borrow_mut().push(record.args().to_string()));
+	}
+
+	fn flush(&self) {}
+}
+
+/// Starts recording the messages logged by every test.
+pub fn capture_logs() {
+	let _ = log::set_logger(&CapturingLogger);
+	log::set_max_level(LevelFilter::Debug);
+}
+
+/// Gets the messages logged by the calling thread so far.
+pub fn logged() -> Vec<String> {
+	LOGGED.with(|logged| logged.borrow().clone())
+}
@@ -0,0 +1,218 @@
+use wasmparser::{BinaryReader, BinaryReaderError, ExternalKind, Parser, Payload, Validator};
+
+use super::net::error::Error;
+
+/// Checks and transformations applied to the WASM and JS loader of each module
+/// before it is uploaded.
+#[derive(Default)]
+pub struct ModuleOptions {
+	// Whether modules should be run through a full WASM validator
+	pub(crate) validate: bool,
+
+	// Names of functions every module must export to be loadable
+	pub(crate) required_exports: Vec<String>,
+
+	// Whether custom sections should be removed to shrink the module, and
+	// whether the debugging symbols in the "name" section should be kept
+	pub(crate) strip: bool,
+	pub(crate) keep_names: bool,
+
+	// Whether each module's JS loader should be parsed, catching syntax
+	// errors that would otherwise only surface once the DAO loads it
+	pub(crate) validate_loaders: bool,
+
+	// Whether a module whose loader and WASM can't be told apart is an error
+	// rather than a warning
+	pub(crate) strict: bool,
+}
+
+/// Strips the module if enabled, and runs the enabled checks over the
+/// result, returning the WASM that should be uploaded.
+pub fn prepare(name: &str, src: Vec<u8>, options: &ModuleOptions) -> Result<Vec<u8>, Error> {
+	let src = if options.strip {
+		let stripped = strip(&src, options.keep_names)
+			.map_err(|e| Error::InvalidModule(name.to_owned(), e.to_string()))?;
+
+		log::debug!(
+			"stripped module {name} from {} to {} bytes",
+			src.len(),
+			stripped.len()
+		);
+
+		stripped
+	} else {
+		src
+	};
+
+	check(name, &src, options)?;
+
+	Ok(src)
+}
+
+/// Runs the enabled checks over the module's WASM, returning an error naming
+/// the module if any of them fail.
+fn check(name: &str, src: &[u8], checks: &ModuleOptions) -> Result<(), Error> {
+	if checks.validate {
+		Validator::new()
+			.validate_all(src)
+			.map_err(|e| Error::InvalidModule(name.to_owned(), e.to_string()))?;
+
+		log::debug!("validated module {name}");
+	}
+
+	if !checks.required_exports.is_empty() {
+		let exports = exported_functions(src)
+			.map_err(|e| Error::InvalidModule(name.to_owned(), e.to_string()))?;
+		let missing: Vec<&str> = checks
+			.required_exports
+			.iter()
+			.map(String::as_str)
+			.filter(|required| !exports.contains(required))
+			.collect();
+
+		if !missing.is_empty() {
+			return Err(Error::InvalidModule(
+				name.to_owned(),
+				format!("missing required exports: {}", missing.join(", ")),
+			));
+		}
+	}
+
+	Ok(())
+}
+
+/// Gets the names of all of the functions exported by the module.
+fn exported_functions(src: &[u8]) -> Result<Vec<&str>, BinaryReaderError> {
+	let mut exports = Vec::new();
+
+	for payload in Parser::new(0).parse_all(src) {
+		if let Payload::ExportSection(reader) = payload? {
+			for export in reader {
+				let export = export?;
+
+				if export.kind == ExternalKind::Func {
+					exports.push(export.name);
+				}
+			}
+		}
+	}
+
+	Ok(exports)
+}
+
+/// Removes all custom sections from the module, except for the "name" section
+/// if debugging symbols should be kept. Custom sections have no effect on
+/// execution, so the stripped module behaves identically.
+fn strip(src: &[u8], keep_names: bool) -> Result<Vec<u8>, BinaryReaderError> {
+	let mut reader = BinaryReader::new(src);
+
+	// The magic number and version are kept as-is
+	let mut stripped = reader.read_bytes(8)?.to_vec();
+
+	while !reader.eof() {
+		let start = reader.current_position();
+		let id = reader.read_u8()?;
+		let size = reader.read_var_u32()? as usize;
+		let contents = reader.read_bytes(size)?;
+
+		// Custom sections have an id of 0, and are prefixed with their name
+		if id == 0 && !(keep_names && BinaryReader::new(contents).read_string()? == "name") {
+			continue;
+		}
+
+		stripped.extend_from_slice(&src[start..reader.current_position()]);
+	}
+
+	Ok(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A module that exports an empty function named "run".
+	const MODULE: &[u8] = &[
+		0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+		0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+		0x03, 0x02, 0x01, 0x00, // function section
+		0x07, 0x07, 0x01, 0x03, b'r', b'u', b'n', 0x00, 0x00, // export section
+		0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+	];
+
+	/// An empty "name" section, and a custom section of other data.
+	const NAME_SECTION: &[u8] = &[0x00, 0x05, 0x04, b'n', b'a', b'm', b'e'];
+	const META_SECTION: &[u8] = &[0x00, 0x07, 0x04, b'm', b'e', b't', b'a', 0x01, 0x02];
+
+	fn with_custom_sections() -> Vec<u8> {
+		[MODULE, NAME_SECTION, META_SECTION].concat()
+	}
+
+	fn options(validate: bool, required_exports: &[&str]) -> ModuleOptions {
+		ModuleOptions {
+			validate,
+			required_exports: required_exports.iter().map(|e| e.to_string()).collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn checks_valid_modules() {
+		assert!(check("valid", MODULE, &options(true, &["run"])).is_ok());
+		assert!(check("valid", &with_custom_sections(), &options(true, &[])).is_ok());
+	}
+
+	#[test]
+	fn rejects_corrupt_modules() {
+		let corrupt = &MODULE[..MODULE.len() - 1];
+
+		assert!(matches!(
+			check("corrupt", corrupt, &options(true, &[])),
+			Err(Error::InvalidModule(name, _)) if name == "corrupt"
+		));
+
+		// Nothing is checked unless asked
+		assert!(check("corrupt", corrupt, &options(false, &[])).is_ok());
+	}
+
+	#[test]
+	fn rejects_missing_exports() {
+		assert!(matches!(
+			check("missing", MODULE, &options(false, &["run", "init", "stop"])),
+			Err(Error::InvalidModule(name, e))
+				if name == "missing" && e == "missing required exports: init, stop"
+		));
+	}
+
+	#[test]
+	fn strips_custom_sections() {
+		assert_eq!(strip(&with_custom_sections(), false).unwrap(), MODULE);
+		assert_eq!(strip(MODULE, false).unwrap(), MODULE);
+	}
+
+	#[test]
+	fn keeps_names_if_asked() {
+		assert_eq!(
+			strip(&with_custom_sections(), true).unwrap(),
+			[MODULE, NAME_SECTION].concat()
+		);
+	}
+
+	#[test]
+	fn fails_to_strip_corrupt_modules() {
+		// The code section claims more bytes than there are
+		assert!(strip(&MODULE[..MODULE.len() - 1], false).is_err());
+	}
+
+	#[test]
+	fn prepares_stripped_modules() {
+		let options = ModuleOptions {
+			strip: true,
+			..options(true, &["run"])
+		};
+
+		assert_eq!(
+			prepare("module", with_custom_sections(), &options).unwrap(),
+			MODULE
+		);
+	}
+}